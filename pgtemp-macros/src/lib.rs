@@ -0,0 +1,226 @@
+#![warn(missing_docs)] // denied in CI
+
+//! Procedural macros for pgtemp. You probably want to use these via the `macros` feature of the
+//! `pgtemp` crate, which re-exports them, rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::meta::ParseNestedMeta;
+use syn::{parse_macro_input, FnArg, ItemFn, Lit, LitStr, Type};
+
+/// Run a test with a temporary PostgreSQL server.
+///
+/// A new `PgTempDB` is started before the test body runs and shut down after it finishes, even if
+/// it panics. The test function may take a single argument, which is either the database itself
+/// (`PgTempDB` or `&PgTempDB`) or its connection URI (`String` or `&str`). Both sync and `async`
+/// functions are supported; async tests are run on a current-thread tokio runtime and the database
+/// is shut down asynchronously afterwards.
+///
+/// The attribute's arguments map onto `PgTempDBBuilder` options:
+///
+/// ```ignore
+/// #[pgtemp::test(
+///     dbname = "mydb",
+///     load = "fixtures/data.sql",
+///     migrations = "migrations",
+///     config(max_connections = "50", geqo = "off"),
+/// )]
+/// async fn my_test(db: &pgtemp::PgTempDB) {
+///     // ...
+/// }
+/// ```
+///
+/// Note that if the test takes `PgTempDB` by value, it is responsible for the database, which will
+/// be dropped (and therefore shut down synchronously) at the end of the test function.
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut builder_calls = Vec::new();
+    let arg_parser = syn::meta::parser(|meta| parse_builder_arg(&meta, &mut builder_calls));
+    parse_macro_input!(args with arg_parser);
+
+    let input = parse_macro_input!(item as ItemFn);
+
+    match expand_test(&input, &builder_calls) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn parse_builder_arg(
+    meta: &ParseNestedMeta<'_>,
+    builder_calls: &mut Vec<TokenStream2>,
+) -> syn::Result<()> {
+    if meta.path.is_ident("dbname") {
+        let dbname: LitStr = meta.value()?.parse()?;
+        builder_calls.push(quote! { .with_dbname(#dbname) });
+    } else if meta.path.is_ident("load") {
+        let path: LitStr = meta.value()?.parse()?;
+        builder_calls.push(quote! { .load_database(::std::path::Path::new(#path)) });
+    } else if meta.path.is_ident("migrations") {
+        let path: LitStr = meta.value()?.parse()?;
+        builder_calls.push(quote! { .with_migrations(#path) });
+    } else if meta.path.is_ident("config") {
+        meta.parse_nested_meta(|param| {
+            let key = param
+                .path
+                .get_ident()
+                .ok_or_else(|| param.error("expected a configuration parameter name"))?
+                .to_string();
+            let value = match param.value()?.parse()? {
+                Lit::Str(s) => s.value(),
+                Lit::Int(i) => i.to_string(),
+                Lit::Float(f) => f.to_string(),
+                Lit::Bool(b) => if b.value { "on" } else { "off" }.to_string(),
+                lit => return Err(syn::Error::new_spanned(lit, "unsupported config value")),
+            };
+            builder_calls.push(quote! { .with_config_param(#key, #value) });
+            Ok(())
+        })?;
+    } else {
+        return Err(meta.error(
+            "unsupported pgtemp::test argument, expected one of `dbname`, `load`, `migrations` or `config`",
+        ));
+    }
+    Ok(())
+}
+
+/// How the database is passed to the test function
+enum Injection {
+    None,
+    OwnedDb,
+    BorrowedDb,
+    OwnedUri,
+    BorrowedUri,
+}
+
+fn injection_for(input: &ItemFn) -> syn::Result<Injection> {
+    let mut inputs = input.sig.inputs.iter();
+    let Some(arg) = inputs.next() else {
+        return Ok(Injection::None);
+    };
+    if let Some(extra) = inputs.next() {
+        return Err(syn::Error::new_spanned(
+            extra,
+            "pgtemp::test functions take at most one argument",
+        ));
+    }
+
+    let FnArg::Typed(arg) = arg else {
+        return Err(syn::Error::new_spanned(
+            arg,
+            "pgtemp::test cannot be used on methods",
+        ));
+    };
+
+    let injection = match &*arg.ty {
+        Type::Reference(reference) => match last_segment(&reference.elem).as_deref() {
+            Some("PgTempDB") => Some(Injection::BorrowedDb),
+            Some("str") => Some(Injection::BorrowedUri),
+            _ => None,
+        },
+        ty => match last_segment(ty).as_deref() {
+            Some("PgTempDB") => Some(Injection::OwnedDb),
+            Some("String") => Some(Injection::OwnedUri),
+            _ => None,
+        },
+    };
+
+    injection.ok_or_else(|| {
+        syn::Error::new_spanned(
+            &arg.ty,
+            "pgtemp::test functions take either a `PgTempDB`, `&PgTempDB`, `String` or `&str` argument",
+        )
+    })
+}
+
+fn last_segment(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    }
+}
+
+fn expand_test(input: &ItemFn, builder_calls: &[TokenStream2]) -> syn::Result<TokenStream2> {
+    let injection = injection_for(input)?;
+
+    let attrs = &input.attrs;
+    let vis = &input.vis;
+    let name = &input.sig.ident;
+    let output = &input.sig.output;
+    let is_async = input.sig.asyncness.is_some();
+
+    // the original function is kept as-is inside the generated test so that its arguments and
+    // return type work the same way as they were written
+    let mut inner = input.clone();
+    inner.attrs.clear();
+    inner.vis = syn::Visibility::Inherited;
+    inner.sig.ident = syn::Ident::new("__pgtemp_test", name.span());
+    let inner_tokens = inner.to_token_stream();
+
+    let builder = quote! { ::pgtemp::PgTempDBBuilder::new() #(#builder_calls)* };
+    let call_arg = match injection {
+        Injection::None => quote! {},
+        Injection::OwnedDb => quote! { db },
+        Injection::BorrowedDb => quote! { &db },
+        Injection::OwnedUri => quote! { db.connection_uri() },
+        Injection::BorrowedUri => quote! { &db.connection_uri() },
+    };
+    let await_token = if is_async {
+        quote! { .await }
+    } else {
+        quote! {}
+    };
+    let call = quote! { __pgtemp_test(#call_arg) #await_token };
+
+    let body = match (is_async, injection) {
+        // the test owns the database, so it is dropped when the test function returns or unwinds
+        (false, Injection::OwnedDb) => quote! {
+            let db = #builder.start();
+            #call
+        },
+        (true, Injection::OwnedDb) => quote! {
+            ::pgtemp::__private::tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("failed to build tokio runtime")
+                .block_on(async {
+                    let db = #builder.start_async().await;
+                    #call
+                })
+        },
+        (false, _) => quote! {
+            let db = #builder.start();
+            let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| #call));
+            db.shutdown();
+            match result {
+                Ok(output) => output,
+                Err(panic) => ::std::panic::resume_unwind(panic),
+            }
+        },
+        (true, _) => quote! {
+            ::pgtemp::__private::tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("failed to build tokio runtime")
+                .block_on(async {
+                    let db = #builder.start_async().await;
+                    let result = ::pgtemp::__private::CatchUnwind::new(async { #call }).await;
+                    db.async_shutdown().await;
+                    match result {
+                        Ok(output) => output,
+                        Err(panic) => ::std::panic::resume_unwind(panic),
+                    }
+                })
+        },
+    };
+
+    Ok(quote! {
+        #[::core::prelude::v1::test]
+        #(#attrs)*
+        #vis fn #name() #output {
+            #inner_tokens
+            #body
+        }
+    })
+}
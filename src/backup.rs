@@ -0,0 +1,286 @@
+use std::fmt;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+use crate::run_db::{self, quote_literal};
+use crate::{PgTempDB, PgTempDBBuilder};
+
+/// The name of the directory in the temp dir that WAL is archived to
+pub(crate) const WAL_ARCHIVE_DIR_NAME: &str = "wal_archive";
+
+/// How long to wait for WAL to be archived or for a restored server to finish recovery
+const TIMEOUT: Duration = Duration::from_secs(30);
+const POLL_DELAY: Duration = Duration::from_millis(50);
+
+/// The point a server restored with
+/// [`PgTempDBBuilder::restore_from_backup`](crate::PgTempDBBuilder::restore_from_backup) is
+/// recovered to. See the [PostgreSQL
+/// docs](https://www.postgresql.org/docs/current/runtime-config-wal.html#RUNTIME-CONFIG-WAL-RECOVERY-TARGET).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecoveryTarget {
+    /// Replay all of the archived WAL
+    Latest,
+    /// Replay the transactions committed up to and including this time, e.g.
+    /// `2024-01-01 12:00:00.123+00` as returned by `SELECT now()`
+    Time(String),
+    /// Replay the WAL up to and including this location, e.g. `0/3000060` as returned by
+    /// `SELECT pg_current_wal_lsn()`
+    Lsn(String),
+    /// Replay the WAL up to the restore point with this name, created with
+    /// [`PgTempDB::create_restore_point`]
+    RestorePoint(String),
+}
+
+impl RecoveryTarget {
+    /// The server configuration parameters that set this target
+    fn server_configs(&self) -> Vec<(&'static str, String)> {
+        let target = match self {
+            RecoveryTarget::Latest => return Vec::new(),
+            RecoveryTarget::Time(time) => ("recovery_target_time", time.clone()),
+            RecoveryTarget::Lsn(lsn) => ("recovery_target_lsn", lsn.clone()),
+            RecoveryTarget::RestorePoint(name) => ("recovery_target_name", name.clone()),
+        };
+        // become a normal server once the target is reached, rather than pausing
+        vec![target, ("recovery_target_action", "promote".into())]
+    }
+}
+
+/// A base backup of a [`PgTempDB`]'s data directory taken with `pg_basebackup`, which can be
+/// restored to a new server with
+/// [`PgTempDBBuilder::restore_from_backup`](crate::PgTempDBBuilder::restore_from_backup). Created
+/// with [`PgTempDB::base_backup`]. Upon drop, the backup is deleted.
+///
+/// The backup borrows the server it was taken of, since restoring it replays WAL from that
+/// server's archive, which is deleted along with the server's temp dir.
+pub struct PgTempBackup<'db> {
+    temp_dir: TempDir,
+    /// the WAL archive of the server the backup was taken of
+    wal_archive: PathBuf,
+    _source: PhantomData<&'db PgTempDB>,
+}
+
+impl PgTempBackup<'_> {
+    /// Returns the path to the backed up data directory
+    pub fn data_dir(&self) -> PathBuf {
+        self.temp_dir.path().join("pg_data_dir")
+    }
+
+    /// Returns the path to the WAL archive of the server the backup was taken of, which WAL is
+    /// restored from when the backup is restored
+    pub fn wal_archive_dir(&self) -> &Path {
+        &self.wal_archive
+    }
+}
+
+impl Debug for PgTempBackup<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PgTempBackup")
+            .field("data dir", &self.data_dir())
+            .field("wal archive dir", &self.wal_archive)
+            .finish()
+    }
+}
+
+impl PgTempDB {
+    /// Returns the directory that completed WAL segments are archived to, if the server was
+    /// started with
+    /// [`PgTempDBBuilder::with_wal_archiving`](crate::PgTempDBBuilder::with_wal_archiving).
+    pub fn wal_archive_dir(&self) -> Option<PathBuf> {
+        let dir = self.temp_dir.as_ref()?.path().join(WAL_ARCHIVE_DIR_NAME);
+        dir.is_dir().then_some(dir)
+    }
+
+    /// Take a base backup of the server with `pg_basebackup`, which can be restored to a new
+    /// server recovered to a later point in time with
+    /// [`PgTempDBBuilder::restore_from_backup`](crate::PgTempDBBuilder::restore_from_backup).
+    /// The backup is stored next to the server's temp dir.
+    ///
+    /// Panics if the server wasn't started with
+    /// [`PgTempDBBuilder::with_wal_archiving`](crate::PgTempDBBuilder::with_wal_archiving).
+    pub fn base_backup(&self) -> PgTempBackup<'_> {
+        let wal_archive = self.wal_archive_dir().expect(
+            "base backups require WAL archiving: start the server with \
+             PgTempDBBuilder::with_wal_archiving",
+        );
+        let parent = self
+            .temp_dir
+            .as_ref()
+            .and_then(|temp_dir| temp_dir.path().parent())
+            .map_or_else(std::env::temp_dir, Path::to_path_buf);
+        let temp_dir =
+            TempDir::with_prefix_in("pgtemp-backup-", parent).expect("failed to create backup dir");
+        if run_db::current_user_is_root() {
+            run_db::chown_to_postgres(temp_dir.path());
+        }
+
+        let backup = PgTempBackup {
+            temp_dir,
+            wal_archive,
+            _source: PhantomData,
+        };
+        run_db::base_backup(
+            self.bin_path.as_deref(),
+            &self.connection_uri(),
+            &backup.data_dir(),
+            false,
+        );
+        backup
+    }
+
+    /// Create a named restore point with `pg_create_restore_point`, which can be used as a
+    /// [`RecoveryTarget::RestorePoint`]. Returns its WAL location.
+    pub fn create_restore_point(&self, name: &str) -> String {
+        let sql = format!("SELECT pg_create_restore_point({})", quote_literal(name));
+        self.query(&sql)[0][0]
+            .clone()
+            .expect("pg_create_restore_point returned NULL")
+    }
+
+    /// Switch to a new WAL segment and wait until the previous one has been archived, so that
+    /// every change made so far can be restored from the archive. Completed WAL segments are
+    /// archived automatically, but the current one is not, so call this before restoring a
+    /// backup to a recent point.
+    ///
+    /// Panics if the segment isn't archived within 30 seconds.
+    pub fn archive_wal(&self) {
+        // the name of the segment that was just completed, or of the previous one if nothing has
+        // been written to the current one yet
+        let segment = self.query("SELECT pg_walfile_name(pg_switch_wal())")[0][0]
+            .clone()
+            .expect("pg_walfile_name returned NULL");
+        let archived_sql = format!(
+            "SELECT coalesce(last_archived_wal >= {}, false) FROM pg_stat_archiver",
+            quote_literal(&segment)
+        );
+
+        let start = Instant::now();
+        while self.query(&archived_sql)[0][0].as_deref() != Some("t") {
+            if start.elapsed() > TIMEOUT {
+                let failure = self.query(
+                    "SELECT failed_count, last_failed_wal, last_failed_time FROM pg_stat_archiver",
+                );
+                panic!(
+                    "WAL segment {} was not archived within {:?}. failed count, last failed \
+                     segment and time: {:?}",
+                    segment, TIMEOUT, failure[0]
+                );
+            }
+            std::thread::sleep(POLL_DELAY);
+        }
+    }
+
+    /// Wait until a server restored from a backup has finished recovery and been promoted
+    pub(crate) fn wait_for_recovery(&mut self) {
+        // fails with the exception while the server is still in recovery
+        let sql = "DO $$ BEGIN IF pg_is_in_recovery() THEN RAISE EXCEPTION 'still in recovery'; \
+                   END IF; END $$";
+        let conn_uri = self.connection_uri();
+        let start = Instant::now();
+        loop {
+            if !self.is_running() {
+                panic!(
+                    "the restored server exited during recovery, e.g. because the recovery \
+                     target was not in the archived WAL: {}",
+                    self.server_output()
+                );
+            }
+            match run_db::try_run_psql(self.bin_path.as_deref(), &conn_uri, sql) {
+                Ok(_) => return,
+                Err(e) if start.elapsed() > TIMEOUT => {
+                    panic!(
+                        "the restored server did not finish recovery within {:?}: {}",
+                        TIMEOUT, e
+                    )
+                }
+                Err(_) => std::thread::sleep(POLL_DELAY),
+            }
+        }
+    }
+
+    /// The last lines of output of a server that has exited, if it wasn't written to a log file
+    fn server_output(&self) -> String {
+        self.server_output
+            .as_ref()
+            .map_or("(server output not available)".into(), |output| {
+                output.tail_after_exit()
+            })
+    }
+}
+
+/// Copy the backup's data directory into a new temp dir, set up to recover to `target` by
+/// restoring WAL from the backup's archive. The builder's server configs are updated with the
+/// recovery settings.
+pub(crate) fn restore_data_dir(
+    builder: &mut PgTempDBBuilder,
+    backup: &PgTempBackup<'_>,
+    target: &RecoveryTarget,
+) -> TempDir {
+    let temp_dir = run_db::create_temp_dir(builder);
+    let data_dir = temp_dir.path().join("pg_data_dir");
+    copy_dir(&backup.data_dir(), &data_dir);
+    std::fs::set_permissions(&data_dir, std::fs::Permissions::from_mode(0o700))
+        .expect(&format!("failed to set permissions of {:?}", data_dir));
+    let signal_path = data_dir.join("recovery.signal");
+    std::fs::write(&signal_path, "").expect(&format!("failed to write {:?}", signal_path));
+    if run_db::current_user_is_root() {
+        run_db::chown_to_postgres(temp_dir.path());
+    }
+
+    let restore_command = format!(
+        "cp '{}/%f' '%p'",
+        backup
+            .wal_archive
+            .to_str()
+            .expect("WAL archive path is not UTF-8")
+    );
+    builder
+        .server_configs
+        .insert("restore_command".into(), restore_command);
+    for (key, val) in target.server_configs() {
+        builder.server_configs.insert(key.into(), val);
+    }
+
+    temp_dir
+}
+
+/// The server configuration parameters that archive completed WAL segments to `archive_dir`
+pub(crate) fn archive_server_configs(archive_dir: &Path) -> Vec<(String, String)> {
+    let archive_dir = archive_dir.to_str().expect("WAL archive path is not UTF-8");
+    vec![
+        ("archive_mode".into(), "on".into()),
+        (
+            "archive_command".into(),
+            format!(
+                "test ! -f '{dir}/%f' && cp '%p' '{dir}/%f'",
+                dir = archive_dir
+            ),
+        ),
+    ]
+}
+
+/// Recursively copy the directory `from` to `to`, which must not exist yet
+fn copy_dir(from: &Path, to: &Path) {
+    std::fs::create_dir(to).expect(&format!("failed to create {:?}", to));
+    let entries = std::fs::read_dir(from).expect(&format!("failed to read {:?}", from));
+    for entry in entries {
+        let entry = entry.expect(&format!("failed to read {:?}", from));
+        let target = to.join(entry.file_name());
+        let file_type = entry
+            .file_type()
+            .expect(&format!("failed to read {:?}", entry.path()));
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            let _bytes = std::fs::copy(entry.path(), &target).expect(&format!(
+                "failed to copy {:?} to {:?}",
+                entry.path(),
+                target
+            ));
+        }
+    }
+}
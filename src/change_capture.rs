@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::run_db::{self, quote_literal};
+use crate::PgTempDB;
+
+/// Used to give each capture's replication slot a unique name
+static NEXT_CAPTURE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The kind of a [`RowChange`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// A row was inserted
+    Insert,
+    /// A row was updated
+    Update,
+    /// A row was deleted
+    Delete,
+}
+
+/// A single row change captured by a [`ChangeCapture`]. Values are in postgres's text format, with
+/// `None` for NULLs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowChange {
+    /// Whether the row was inserted, updated or deleted
+    pub kind: ChangeKind,
+    /// The schema of the table the row is in, e.g. `public`
+    pub schema: String,
+    /// The name of the table the row is in
+    pub table: String,
+    /// For inserts and updates, the new row. For deletes, the old row's replica identity columns
+    /// (by default its primary key), or nothing if the table has no replica identity.
+    pub columns: BTreeMap<String, Option<String>>,
+    /// For updates that change the replica identity columns, or of tables with
+    /// `REPLICA IDENTITY FULL`, the old values of those columns
+    pub old_key: Option<BTreeMap<String, Option<String>>>,
+}
+
+impl RowChange {
+    /// Returns the value of `column`, or `None` if it is NULL or not part of the change
+    pub fn get(&self, column: &str) -> Option<&str> {
+        self.columns.get(column)?.as_deref()
+    }
+}
+
+/// A handle to a logical replication slot that records the row changes made in a database, so
+/// that tests can assert on exactly which changes some code made. Created with
+/// [`PgTempDB::start_change_capture`]. Upon drop, the slot is dropped.
+///
+/// The handle borrows the [`PgTempDB`], so that it is dropped before the server is shut down.
+pub struct ChangeCapture<'db> {
+    slot_name: String,
+    conn_uri: String,
+    bin_path: Option<PathBuf>,
+    _server: PhantomData<&'db PgTempDB>,
+}
+
+impl PgTempDB {
+    /// Start recording the row changes made in this database, using a logical replication slot
+    /// with postgres's built-in `test_decoding` plugin. See [`ChangeCapture`].
+    ///
+    /// The server must have been started with
+    /// [`PgTempDBBuilder::with_change_capture`](crate::PgTempDBBuilder::with_change_capture), which
+    /// configures `wal_level=logical`.
+    pub fn start_change_capture(&self) -> ChangeCapture<'_> {
+        let wal_level = self.query("SELECT current_setting('wal_level')");
+        assert!(
+            wal_level[0][0].as_deref() == Some("logical"),
+            "change capture requires wal_level=logical, but it is {}: start the server with \
+             PgTempDBBuilder::with_change_capture",
+            wal_level[0][0].as_deref().unwrap_or("unset")
+        );
+
+        let capture = ChangeCapture {
+            slot_name: format!(
+                "pgtemp_capture_{}_{}",
+                std::process::id(),
+                NEXT_CAPTURE_ID.fetch_add(1, Ordering::Relaxed)
+            ),
+            conn_uri: self.connection_uri(),
+            bin_path: self.bin_path.clone(),
+            _server: PhantomData,
+        };
+        let sql = format!(
+            "SELECT pg_create_logical_replication_slot({}, 'test_decoding')",
+            quote_literal(&capture.slot_name)
+        );
+        run_db::run_psql(capture.bin_path.as_deref(), &capture.conn_uri, &sql);
+
+        capture
+    }
+}
+
+impl ChangeCapture<'_> {
+    /// Returns the row changes committed since the capture was started or since the last call,
+    /// in commit order. Truncates and changes to other databases are not included.
+    pub fn changes(&self) -> Vec<RowChange> {
+        // changes are only decoded up to the flushed WAL, and with `synchronous_commit` off (as
+        // with the default server profile) the latest commits may not have been flushed yet.
+        // synchronously committing a transaction that writes WAL flushes them. the message it
+        // writes is skipped below, along with the transaction's BEGIN and COMMIT.
+        run_db::run_psql(
+            self.bin_path.as_deref(),
+            &self.conn_uri,
+            "SET synchronous_commit = on; SELECT pg_logical_emit_message(true, 'pgtemp', '')",
+        );
+
+        let sql = format!(
+            "SELECT data FROM pg_logical_slot_get_changes({}, NULL, NULL, \
+             'include-xids', '0', 'skip-empty-xacts', '1')",
+            quote_literal(&self.slot_name)
+        );
+        run_db::run_psql_query(self.bin_path.as_deref(), &self.conn_uri, &sql)
+            .into_iter()
+            .filter_map(|row| row.into_iter().next().flatten())
+            .filter_map(|line| {
+                parse_change(&line)
+                    .unwrap_or_else(|| panic!("failed to parse logical decoding output: {}", line))
+            })
+            .collect()
+    }
+
+    /// Returns the name of the replication slot
+    pub fn slot_name(&self) -> &str {
+        &self.slot_name
+    }
+}
+
+impl Debug for ChangeCapture<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChangeCapture")
+            .field("slot name", &self.slot_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for ChangeCapture<'_> {
+    fn drop(&mut self) {
+        let sql = format!(
+            "SELECT pg_drop_replication_slot({})",
+            quote_literal(&self.slot_name)
+        );
+        let res = run_db::try_run_psql(self.bin_path.as_deref(), &self.conn_uri, &sql);
+        // don't turn an existing panic into an abort if e.g. the server is already gone
+        if let Err(e) = res {
+            if !std::thread::panicking() {
+                panic!(
+                    "failed to drop replication slot `{}`: {}",
+                    self.slot_name, e
+                );
+            }
+        }
+    }
+}
+
+/// Parse a line of `test_decoding` output, e.g.
+/// `table public.items: UPDATE: id[integer]:1 name[text]:'a'`. Returns `Some(None)` for lines
+/// that aren't row changes, like `BEGIN`, `COMMIT`, messages and truncates, and `None` if the line
+/// can't be parsed.
+fn parse_change(line: &str) -> Option<Option<RowChange>> {
+    let Some(rest) = line.strip_prefix("table ") else {
+        return Some(None);
+    };
+    let (schema, rest) = parse_ident(rest)?;
+    let rest = rest.strip_prefix('.')?;
+    let (table, rest) = parse_ident(rest)?;
+    if rest.starts_with(", ") {
+        // only truncates list several tables
+        return Some(None);
+    }
+    let (action, rest) = rest.strip_prefix(": ")?.split_once(':')?;
+    let rest = rest.strip_prefix(' ').unwrap_or(rest);
+
+    let kind = match action {
+        "INSERT" => ChangeKind::Insert,
+        "UPDATE" => ChangeKind::Update,
+        "DELETE" => ChangeKind::Delete,
+        _ => return Some(None),
+    };
+
+    let (old_key, rest) = match rest.strip_prefix("old-key: ") {
+        Some(rest) => {
+            let (old_key, rest) = parse_tuple(rest)?;
+            (Some(old_key), rest.strip_prefix("new-tuple: ")?)
+        }
+        None => (None, rest),
+    };
+    let (columns, rest) = if rest == "(no-tuple-data)" {
+        (BTreeMap::new(), "")
+    } else {
+        parse_tuple(rest)?
+    };
+    if !rest.is_empty() {
+        return None;
+    }
+
+    Some(Some(RowChange {
+        kind,
+        schema,
+        table,
+        columns,
+        old_key,
+    }))
+}
+
+type Tuple = BTreeMap<String, Option<String>>;
+
+/// Parse space-separated `name[type]:value` columns, stopping at the end of the input or at a
+/// `new-tuple:` marker. Returns the columns and the rest of the input.
+fn parse_tuple(mut rest: &str) -> Option<(Tuple, &str)> {
+    let mut columns = BTreeMap::new();
+    while !rest.is_empty() && !rest.starts_with("new-tuple: ") {
+        let (name, after_name) = parse_ident(rest)?;
+        // the type name may itself contain brackets, e.g. `integer[]`
+        let (_type_name, after_type) = after_name.strip_prefix('[')?.split_once("]:")?;
+        let (value, after_value) = parse_value(after_type)?;
+        columns.insert(name, value);
+        rest = after_value.strip_prefix(' ').unwrap_or(after_value);
+    }
+    Some((columns, rest))
+}
+
+/// Parse a value, which is either `null`, a single-quoted literal, or an unquoted value (numbers
+/// and booleans) that ends at the next space.
+fn parse_value(s: &str) -> Option<(Option<String>, &str)> {
+    if let Some(quoted) = s.strip_prefix('\'') {
+        let (value, rest) = parse_quoted(quoted, '\'')?;
+        return Some((Some(value), rest));
+    }
+    let end = s.find(' ').unwrap_or(s.len());
+    let (value, rest) = s.split_at(end);
+    if value == "null" {
+        Some((None, rest))
+    } else {
+        Some((Some(value.to_string()), rest))
+    }
+}
+
+/// Parse an identifier, which is either double-quoted or ends at the next `.`, `,`, `:` or `[`.
+fn parse_ident(s: &str) -> Option<(String, &str)> {
+    if let Some(quoted) = s.strip_prefix('"') {
+        return parse_quoted(quoted, '"');
+    }
+    let end = s.find(['.', ',', ':', '['])?;
+    Some((s[..end].to_string(), &s[end..]))
+}
+
+/// Parse the rest of a string quoted with `quote`, in which the quote character is escaped by
+/// doubling it. Returns the unescaped string and the input after the closing quote.
+fn parse_quoted(s: &str, quote: char) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut rest = s;
+    loop {
+        let end = rest.find(quote)?;
+        value.push_str(&rest[..end]);
+        rest = &rest[end + 1..];
+        match rest.strip_prefix(quote) {
+            Some(after_escaped) => {
+                value.push(quote);
+                rest = after_escaped;
+            }
+            None => return Some((value, rest)),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::{PgTempDB, PgTempDBBuilder, PgTempDatabase};
+
+/// A handle to a local PostgreSQL server that hands out disposable databases, rather than
+/// providing a single database like [`PgTempDB`]. Each call to [`Self::create_database`] creates
+/// a new database with a unique name, copied from the cluster's main database (so any script
+/// loaded with [`PgTempDBBuilder::load_database`] is present in every database), which is dropped
+/// again when the returned [`PgTempDatabase`] is dropped.
+///
+/// This is the library equivalent of running the pgtemp daemon in `--single` mode and creating a
+/// new database for each test. The server itself is shut down when the cluster is dropped, so all
+/// databases should be dropped before the cluster.
+pub struct PgTempCluster {
+    // the server's main database is only used as the template for new databases
+    server: PgTempDB,
+    max_databases: Option<usize>,
+    databases_in_use: Arc<(Mutex<usize>, Condvar)>,
+    next_database_id: AtomicUsize,
+}
+
+impl PgTempCluster {
+    /// Start a PgTempCluster with the parameters configured from a PgTempDBBuilder
+    pub fn from_builder(builder: PgTempDBBuilder) -> PgTempCluster {
+        let max_databases = builder.max_databases;
+        let server = PgTempDB::from_builder(builder);
+
+        PgTempCluster {
+            server,
+            max_databases,
+            databases_in_use: Arc::new((Mutex::new(0), Condvar::new())),
+            next_database_id: AtomicUsize::new(0),
+        }
+    }
+
+    /// Creates a new PgTempCluster with default configuration and starts a PostgreSQL server.
+    pub fn new() -> PgTempCluster {
+        PgTempDBBuilder::new().start_cluster()
+    }
+
+    /// Creates a new database with a unique name on the server.
+    ///
+    /// If the cluster was configured with [`PgTempDBBuilder::with_max_databases`] and that many
+    /// databases currently exist, this blocks until one of them is dropped. Be careful not to call
+    /// this from an async context where the databases are dropped on the same thread.
+    pub fn create_database(&self) -> PgTempDatabase {
+        let (in_use, released) = &*self.databases_in_use;
+        let mut count = in_use.lock().expect("database count lock poisoned");
+        if let Some(max) = self.max_databases {
+            while *count >= max {
+                count = released.wait(count).expect("database count lock poisoned");
+            }
+        }
+        *count += 1;
+        drop(count);
+
+        self.create_database_with_permit()
+    }
+
+    /// Like [`Self::create_database`], but returns `None` instead of blocking if the maximum
+    /// number of databases already exist.
+    pub fn try_create_database(&self) -> Option<PgTempDatabase> {
+        let (in_use, _released) = &*self.databases_in_use;
+        let mut count = in_use.lock().expect("database count lock poisoned");
+        if self.max_databases.is_some_and(|max| *count >= max) {
+            return None;
+        }
+        *count += 1;
+        drop(count);
+
+        Some(self.create_database_with_permit())
+    }
+
+    fn create_database_with_permit(&self) -> PgTempDatabase {
+        // the permit is created first so that the count is decremented even if creation fails
+        let permit = DatabasePermit(self.databases_in_use.clone());
+
+        let id = self.next_database_id.fetch_add(1, Ordering::Relaxed);
+        let name = format!("pgtempdb_{}", id);
+        let mut db = self.server.create_database_from_template(&name);
+        db.permit = Some(permit);
+
+        db
+    }
+
+    /// Returns the number of databases created by this cluster that have not been dropped yet.
+    pub fn databases_in_use(&self) -> usize {
+        *self
+            .databases_in_use
+            .0
+            .lock()
+            .expect("database count lock poisoned")
+    }
+
+    /// Returns the underlying [`PgTempDB`], e.g. to get the server's port or data directory.
+    pub fn server(&self) -> &PgTempDB {
+        &self.server
+    }
+
+    /// Shut down the server and delete its data directory. Equivalent to calling drop on this
+    /// struct. See [`PgTempDB::shutdown`].
+    pub fn shutdown(self) {
+        drop(self);
+    }
+}
+
+impl Debug for PgTempCluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PgTempCluster")
+            .field("server", &self.server)
+            .field("max databases", &self.max_databases)
+            .field("databases in use", &self.databases_in_use())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Counts towards a [`PgTempCluster`]'s maximum number of databases until it is dropped along
+/// with the [`PgTempDatabase`] holding it.
+pub(crate) struct DatabasePermit(Arc<(Mutex<usize>, Condvar)>);
+
+impl Drop for DatabasePermit {
+    fn drop(&mut self) {
+        let (in_use, released) = &*self.0;
+        // don't panic while dropping if another thread panicked while holding the lock
+        let mut count = in_use
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        *count -= 1;
+        released.notify_one();
+    }
+}
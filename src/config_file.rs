@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Deserializer};
+
+use crate::PgTempDBBuilder;
+
+/// The name of the configuration file searched for by
+/// [`PgTempDBBuilder::from_discovered_config_file`].
+pub const CONFIG_FILE_NAME: &str = "pgtemp.toml";
+
+impl PgTempDBBuilder {
+    /// Create a builder from a `pgtemp.toml` configuration file. The keys in the file are the
+    /// names of the builder's fields, and relative paths are relative to the directory containing
+    /// the file:
+    ///
+    /// ```toml
+    /// dbname = "mydb"
+    /// bin_path = "/usr/lib/postgresql/16/bin"
+    /// load_path = "fixtures/schema.sql"
+    /// extensions = ["pgcrypto"]
+    ///
+    /// [server_configs]
+    /// max_connections = 200
+    /// log_statement = "all"
+    ///
+    /// [initdb_args]
+    /// encoding = "UTF8"
+    /// ```
+    ///
+    /// Values in `server_configs` and `initdb_args` may be strings, numbers or booleans, which are
+    /// passed to postgres as `on` or `off`.
+    ///
+    /// `PGTEMP_*` environment variables (see [`Self::from_env`]) are applied on top of the file's
+    /// settings, and explicit builder calls made afterwards take precedence over both.
+    ///
+    /// Panics if the file can't be read or is invalid.
+    pub fn from_config_file(path: impl AsRef<Path>) -> PgTempDBBuilder {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .expect(&format!("failed to read pgtemp config file {:?}", path));
+        let builder: PgTempDBBuilder = toml::from_str(&contents)
+            .unwrap_or_else(|e| panic!("invalid pgtemp config file {:?}: {}", path, e));
+
+        let config_dir = path.parent().unwrap_or(Path::new(""));
+        builder.with_paths_relative_to(config_dir).with_env()
+    }
+
+    /// Search for a `pgtemp.toml` file in the directory given by the `CARGO_MANIFEST_DIR`
+    /// environment variable (which cargo sets when running tests) or the current directory if it
+    /// is unset, and then in each of its parent directories, so that e.g. all the crates in a
+    /// workspace can share a single file at the workspace root.
+    pub fn find_config_file() -> Option<PathBuf> {
+        let start = std::env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .or_else(|| std::env::current_dir().ok())?;
+
+        start
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// Create a builder from the `pgtemp.toml` file found by [`Self::find_config_file`], or from
+    /// the environment only (like [`Self::from_env`]) if there is no such file.
+    pub fn from_discovered_config_file() -> PgTempDBBuilder {
+        match PgTempDBBuilder::find_config_file() {
+            Some(path) => PgTempDBBuilder::from_config_file(path),
+            None => PgTempDBBuilder::from_env(),
+        }
+    }
+
+    fn with_paths_relative_to(mut self, dir: &Path) -> Self {
+        let additional_db_paths = self
+            .additional_databases
+            .iter_mut()
+            .flat_map(|db| [&mut db.load_path, &mut db.migrations_path]);
+        for path in [
+            &mut self.temp_dir_prefix,
+            &mut self.dump_path,
+            &mut self.load_path,
+            &mut self.migrations_path,
+            &mut self.bin_path,
+            &mut self.log_path,
+        ]
+        .into_iter()
+        .chain(additional_db_paths)
+        .flatten()
+        {
+            if path.is_relative() {
+                *path = dir.join(&*path);
+            }
+        }
+        self
+    }
+}
+
+/// Deserialize a map of postgres settings, allowing non-string values like `max_connections = 5`
+pub(crate) fn deserialize_string_map<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        String(String),
+        Integer(i64),
+        Float(f64),
+        Bool(bool),
+    }
+
+    let map = HashMap::<String, Value>::deserialize(deserializer)?;
+    Ok(map
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::String(s) => s,
+                Value::Integer(i) => i.to_string(),
+                Value::Float(f) => f.to_string(),
+                Value::Bool(b) => if b { "on" } else { "off" }.to_string(),
+            };
+            (key, value)
+        })
+        .collect())
+}
@@ -1,4 +1,4 @@
-use crate::{PgTempDB, PgTempDBBuilder};
+use crate::{ConnectionInfo, PgTempDB, PgTempDBBuilder};
 
 use std::net::SocketAddr;
 
@@ -79,7 +79,17 @@ impl PgTempDaemon {
     #[cfg(feature = "cli")]
     /// Create a [`PgTempDaemon`] from the command line args given.
     pub async fn from_args(args: PgTempDaemonArgs) -> PgTempDaemon {
-        let mut builder = PgTempDBBuilder::from_connection_uri(&args.connection_uri);
+        PgTempDaemon::from_args_with_builder(args, PgTempDBBuilder::new()).await
+    }
+
+    #[cfg(feature = "cli")]
+    /// Create a [`PgTempDaemon`] from the command line args given, applied on top of `builder`,
+    /// e.g. one read from a `pgtemp.toml` file with [`PgTempDBBuilder::from_config_file`].
+    pub async fn from_args_with_builder(
+        args: PgTempDaemonArgs,
+        builder: PgTempDBBuilder,
+    ) -> PgTempDaemon {
+        let mut builder = builder.with_connection_uri(&args.connection_uri);
         if let Some(data_dir_prefix) = args.data_dir_prefix {
             builder = builder.with_data_dir_prefix(data_dir_prefix);
         }
@@ -133,13 +143,16 @@ impl PgTempDaemon {
     }
 
     fn conn_uri(&self) -> String {
-        format!(
-            "postgresql://{}:{}@localhost:{}/{}",
-            self.builder.get_user(),
-            self.builder.get_password(),
-            self.port,
-            self.builder.get_dbname()
-        )
+        ConnectionInfo {
+            host: "localhost".into(),
+            port: self.port,
+            socket_dir: None,
+            user: self.builder.get_user(),
+            password: self.builder.get_password(),
+            dbname: self.builder.get_dbname(),
+            ssl_root_cert: None,
+        }
+        .uri()
     }
 
     /// Start the daemon, listening for either TCP connections on the configured port. The server
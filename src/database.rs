@@ -0,0 +1,218 @@
+use std::fmt;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+
+use crate::cluster::DatabasePermit;
+use crate::connection::ConnectionInfo;
+use crate::run_db;
+
+/// A database created on startup in addition to the main one. See
+/// [`PgTempDBBuilder::with_database`](crate::PgTempDBBuilder::with_database).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct AdditionalDatabase {
+    /// The name of the database
+    pub name: String,
+    /// The owner of the database. Default: the superuser
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub owner: Option<String>,
+    /// The script to load into the database via `psql` after creating it
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub load_path: Option<PathBuf>,
+    /// The migrations to run in the database after loading `load_path`. See
+    /// [`PgTempDB::run_migrations`](crate::PgTempDB::run_migrations).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub migrations_path: Option<PathBuf>,
+}
+
+impl AdditionalDatabase {
+    /// An additional database named `name`, owned by the superuser
+    pub fn new(name: &str) -> AdditionalDatabase {
+        AdditionalDatabase {
+            name: name.into(),
+            owner: None,
+            load_path: None,
+            migrations_path: None,
+        }
+    }
+
+    /// Set the owner of the database, e.g. a role created with
+    /// [`PgTempDBBuilder::with_role`](crate::PgTempDBBuilder::with_role)
+    #[must_use]
+    pub fn with_owner(mut self, owner: &str) -> Self {
+        self.owner = Some(owner.into());
+        self
+    }
+
+    /// Load the given script into the database via `psql` after creating it. The script is run
+    /// as the superuser.
+    #[must_use]
+    pub fn load_database(mut self, path: impl AsRef<Path>) -> Self {
+        self.load_path = Some(PathBuf::from(path.as_ref()));
+        self
+    }
+
+    /// Run the migrations in the given directory in the database after loading the script set
+    /// with [`Self::load_database`], if any. The migrations are run as the superuser.
+    #[must_use]
+    pub fn with_migrations(mut self, dir: impl AsRef<Path>) -> Self {
+        self.migrations_path = Some(PathBuf::from(dir.as_ref()));
+        self
+    }
+}
+
+/// A handle to a single database inside an already-running PostgreSQL server, e.g. one created by
+/// [`PgTempDB::create_database_from_template`](crate::PgTempDB::create_database_from_template) or
+/// [`PgTempCluster::create_database`](crate::PgTempCluster::create_database).
+/// Upon drop, the database is dropped with `DROP DATABASE ... WITH (FORCE)`, which terminates any
+/// connections that are still open to it.
+///
+/// The handle does not keep the server alive, so it should be dropped before the server it was
+/// created on is shut down.
+pub struct PgTempDatabase {
+    dbuser: String,
+    dbpass: String,
+    dbport: u16,
+    dbname: String,
+    /// the CA that signed the server's certificate, if the server has TLS enabled
+    ssl_root_cert: Option<PathBuf>,
+    /// the database we connect to in order to drop this one
+    maintenance_dbname: String,
+    bin_path: Option<PathBuf>,
+    /// set when the database was created by a [`PgTempCluster`](crate::PgTempCluster). dropped
+    /// after the database itself is dropped.
+    pub(crate) permit: Option<DatabasePermit>,
+}
+
+impl PgTempDatabase {
+    /// Creates the database `dbname` on the server at `port` by copying `template`.
+    /// `ssl_root_cert` is the server's CA certificate if it has TLS enabled.
+    pub(crate) fn create(
+        dbuser: &str,
+        dbpass: &str,
+        dbport: u16,
+        dbname: &str,
+        template: &str,
+        ssl_root_cert: Option<PathBuf>,
+        bin_path: Option<PathBuf>,
+    ) -> PgTempDatabase {
+        let maintenance_uri = ConnectionInfo {
+            host: "localhost".into(),
+            port: dbport,
+            socket_dir: None,
+            ssl_root_cert: ssl_root_cert.clone(),
+            user: dbuser.into(),
+            password: dbpass.into(),
+            dbname: maintenance_dbname(template).into(),
+        }
+        .uri();
+        let sql = format!(
+            "CREATE DATABASE {} TEMPLATE {}",
+            run_db::quote_ident(dbname),
+            run_db::quote_ident(template)
+        );
+        // the handle is only created once the database exists, so that a failure (e.g. because
+        // the name is taken) doesn't drop a database we didn't create
+        run_db::run_psql(bin_path.as_deref(), &maintenance_uri, &sql);
+
+        PgTempDatabase {
+            dbuser: dbuser.into(),
+            dbpass: dbpass.into(),
+            dbport,
+            dbname: dbname.into(),
+            ssl_root_cert,
+            maintenance_dbname: maintenance_dbname(template).into(),
+            bin_path,
+            permit: None,
+        }
+    }
+
+    /// Returns the database username used when connecting to the postgres server.
+    pub fn db_user(&self) -> &str {
+        &self.dbuser
+    }
+
+    /// Returns the database password used when connecting to the postgres server.
+    pub fn db_pass(&self) -> &str {
+        &self.dbpass
+    }
+
+    /// Returns the port the postgres server is running on.
+    pub fn db_port(&self) -> u16 {
+        self.dbport
+    }
+
+    /// Returns the the name of the database.
+    pub fn db_name(&self) -> &str {
+        &self.dbname
+    }
+
+    /// Returns the details needed to connect to the database, which can be rendered in various
+    /// formats. See [`ConnectionInfo`].
+    pub fn connection_info(&self) -> ConnectionInfo {
+        ConnectionInfo {
+            host: "localhost".into(),
+            port: self.db_port(),
+            socket_dir: None,
+            ssl_root_cert: self.ssl_root_cert.clone(),
+            user: self.db_user().into(),
+            password: self.db_pass().into(),
+            dbname: self.db_name().into(),
+        }
+    }
+
+    /// Returns a connection string that can be passed to a libpq connection function.
+    pub fn connection_string(&self) -> String {
+        self.connection_info().keyword_string()
+    }
+
+    /// Returns a generic connection URI that can be passed to most SQL libraries' connect
+    /// methods.
+    pub fn connection_uri(&self) -> String {
+        self.connection_info().uri()
+    }
+
+    fn maintenance_uri(&self) -> String {
+        self.connection_info()
+            .with_dbname(&self.maintenance_dbname)
+            .uri()
+    }
+}
+
+/// The database we connect to when creating databases from `template` or dropping them. This can't
+/// be the template database itself because postgres won't copy a database with open connections.
+pub(crate) fn maintenance_dbname(template: &str) -> &'static str {
+    if template == "postgres" {
+        "template1"
+    } else {
+        "postgres"
+    }
+}
+
+impl Debug for PgTempDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PgTempDatabase")
+            .field("connection string", &self.connection_string())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for PgTempDatabase {
+    fn drop(&mut self) {
+        let sql = format!(
+            "DROP DATABASE {} WITH (FORCE)",
+            run_db::quote_ident(&self.dbname)
+        );
+        let res = run_db::try_run_psql(self.bin_path.as_deref(), &self.maintenance_uri(), &sql);
+        // don't turn an existing panic into an abort if e.g. the server is already gone
+        if let Err(e) = res {
+            if !std::thread::panicking() {
+                panic!("failed to drop database `{}`: {}", self.dbname, e);
+            }
+        }
+    }
+}
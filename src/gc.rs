@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::run_db;
+
+/// The name of the file in each temp dir that records which process owns the server, so that
+/// [`gc`] can clean up after processes that were killed before they could shut it down.
+pub(crate) const METADATA_FILE_NAME: &str = "pgtemp-metadata";
+
+/// The details of a running server, written to its temp dir on startup.
+pub(crate) struct Metadata {
+    /// the process that started the server and is responsible for stopping it
+    pub(crate) owner_pid: u32,
+    /// the postmaster's pid, from its `postmaster.pid` file
+    pub(crate) postgres_pid: u32,
+    pub(crate) uri: String,
+    /// seconds since the unix epoch
+    pub(crate) created_at: u64,
+    /// whether to keep the data directory after the server is stopped
+    pub(crate) persist: bool,
+}
+
+impl Metadata {
+    pub(crate) fn new(postgres_pid: u32, uri: String, persist: bool) -> Metadata {
+        Metadata {
+            owner_pid: std::process::id(),
+            postgres_pid,
+            uri,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            persist,
+        }
+    }
+
+    pub(crate) fn read(temp_dir: &Path) -> Option<Metadata> {
+        let contents = std::fs::read_to_string(temp_dir.join(METADATA_FILE_NAME)).ok()?;
+        let fields: BTreeMap<&str, &str> = contents
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .collect();
+
+        Some(Metadata {
+            owner_pid: fields.get("owner_pid")?.parse().ok()?,
+            postgres_pid: fields.get("postgres_pid")?.parse().ok()?,
+            uri: (*fields.get("uri")?).to_string(),
+            created_at: fields.get("created_at")?.parse().ok()?,
+            persist: fields.get("persist")?.parse().ok()?,
+        })
+    }
+
+    pub(crate) fn write(&self, temp_dir: &Path) {
+        let contents = format!(
+            "owner_pid={}\npostgres_pid={}\nuri={}\ncreated_at={}\npersist={}\n",
+            self.owner_pid, self.postgres_pid, self.uri, self.created_at, self.persist
+        );
+        let path = temp_dir.join(METADATA_FILE_NAME);
+        std::fs::write(&path, contents).expect(&format!("failed to write {:?}", path));
+    }
+
+    /// Remove the metadata file, so that [`gc`] leaves the temp dir alone
+    pub(crate) fn remove(temp_dir: &Path) {
+        let _res = std::fs::remove_file(temp_dir.join(METADATA_FILE_NAME));
+    }
+}
+
+/// Clean up after processes that were killed (e.g. by a CI timeout) before they could shut down
+/// their servers, in the directories pgtemp creates temp dirs in by default: the
+/// `PGTEMP_DATA_DIR_PREFIX` directory if it is set, or else the system temp dir, and `/dev/shm`
+/// and `$XDG_RUNTIME_DIR` (see
+/// [`PgTempDBBuilder::with_memory_backed_storage`](crate::PgTempDBBuilder::with_memory_backed_storage)).
+/// See [`gc_in`] for details.
+pub fn gc() -> Vec<PathBuf> {
+    let mut dirs =
+        vec![std::env::var_os("PGTEMP_DATA_DIR_PREFIX")
+            .map_or_else(std::env::temp_dir, PathBuf::from)];
+    dirs.push(PathBuf::from("/dev/shm"));
+    dirs.extend(std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from));
+    dirs.dedup();
+
+    dirs.iter().flat_map(gc_in).collect()
+}
+
+/// Clean up the `pgtemp-*` temp dirs in `prefix` whose owning process no longer exists: their
+/// postgres servers are stopped and the directories are deleted, unless they were started with
+/// [`PgTempDBBuilder::persist_data`](crate::PgTempDBBuilder::persist_data). Returns the temp
+/// dirs that were cleaned up.
+///
+/// Temp dirs of servers that are still owned by a running process are left alone, as are servers
+/// shared with [`shared_across_processes`](crate::shared_across_processes), which clean up after
+/// themselves. A temp dir is also left alone, with a message on stderr, if its postmaster is still
+/// running after trying to stop it, e.g. because it couldn't be identified as the recorded server.
+pub fn gc_in(prefix: impl AsRef<Path>) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(prefix.as_ref()) else {
+        return Vec::new();
+    };
+
+    let mut collected = Vec::new();
+    for entry in entries.flatten() {
+        let temp_dir = entry.path();
+        let is_temp_dir = entry.file_name().to_string_lossy().starts_with("pgtemp-")
+            && entry.file_type().is_ok_and(|t| t.is_dir());
+        if !is_temp_dir {
+            continue;
+        }
+        let Some(metadata) = Metadata::read(&temp_dir) else {
+            continue;
+        };
+        if run_db::process_is_running(metadata.owner_pid) {
+            continue;
+        }
+
+        if let Some(postmaster_pid) = run_db::read_postmaster_pid(&temp_dir.join("pg_data_dir")) {
+            // only stop the process if it is the server that was recorded, in case the pid has
+            // been reused since
+            if postmaster_pid == metadata.postgres_pid && run_db::process_is_running(postmaster_pid)
+            {
+                run_db::stop_detached_server(postmaster_pid);
+            }
+            // never delete the data directory from under a running server
+            if run_db::process_is_running(postmaster_pid) {
+                eprintln!(
+                    "pgtemp: skipping {:?}: postmaster {} is still running",
+                    temp_dir, postmaster_pid
+                );
+                continue;
+            }
+        }
+
+        if metadata.persist {
+            Metadata::remove(&temp_dir);
+        } else if let Err(e) = std::fs::remove_dir_all(&temp_dir) {
+            eprintln!("pgtemp: failed to remove {:?}: {}", temp_dir, e);
+            continue;
+        }
+        collected.push(temp_dir);
+    }
+    collected
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+
+/// The kind of connection an [`HbaRule`] matches, i.e. the first field of a `pg_hba.conf` line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum HbaConnectionType {
+    /// Connections via the unix socket
+    Local,
+    /// TCP connections, with or without TLS
+    Host,
+    /// TCP connections using TLS
+    HostSsl,
+    /// TCP connections not using TLS
+    HostNoSsl,
+}
+
+/// How connections matching an [`HbaRule`] are authenticated. See the [PostgreSQL
+/// docs](https://www.postgresql.org/docs/current/auth-methods.html) for details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum HbaAuthMethod {
+    /// Allow the connection without a password
+    Trust,
+    /// Reject the connection
+    Reject,
+    /// Require a password, using SCRAM-SHA-256 authentication
+    #[cfg_attr(feature = "serde", serde(rename = "scram-sha-256"))]
+    ScramSha256,
+    /// Require a password, using MD5 or SCRAM-SHA-256 authentication
+    Md5,
+    /// Require a password sent in clear text
+    Password,
+    /// Require a TLS client certificate whose common name is the user's name
+    Cert,
+    /// Use the operating system user's name as the user's name (unix socket only)
+    Peer,
+}
+
+/// A single rule (line) in the server's `pg_hba.conf`, which controls which connections are
+/// allowed and how they are authenticated. Rules are checked in order, and the first one that
+/// matches a connection is used. See [`PgTempDBBuilder::with_hba_rule`](crate::PgTempDBBuilder::with_hba_rule).
+///
+/// ```
+/// use pgtemp::{HbaAuthMethod, HbaRule};
+///
+/// let rule = HbaRule::host("127.0.0.1/32", HbaAuthMethod::ScramSha256).user("app");
+/// assert_eq!(rule.to_string(), "host all app 127.0.0.1/32 scram-sha-256");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HbaRule {
+    /// The kind of connection the rule matches
+    pub connection_type: HbaConnectionType,
+    /// The database(s) the rule matches. Default: `all`
+    pub database: String,
+    /// The user(s) the rule matches. Default: `all`
+    pub user: String,
+    /// The client address(es) the rule matches, for rules other than `local` ones
+    pub address: Option<String>,
+    /// How matching connections are authenticated
+    pub method: HbaAuthMethod,
+}
+
+impl HbaRule {
+    /// A rule for connections via the unix socket
+    pub fn local(method: HbaAuthMethod) -> HbaRule {
+        HbaRule::new(HbaConnectionType::Local, None, method)
+    }
+
+    /// A rule for TCP connections from `address` (e.g. `127.0.0.1/32`), with or without TLS
+    pub fn host(address: &str, method: HbaAuthMethod) -> HbaRule {
+        HbaRule::new(HbaConnectionType::Host, Some(address), method)
+    }
+
+    /// A rule for TCP connections from `address` using TLS
+    pub fn hostssl(address: &str, method: HbaAuthMethod) -> HbaRule {
+        HbaRule::new(HbaConnectionType::HostSsl, Some(address), method)
+    }
+
+    /// A rule for TCP connections from `address` not using TLS
+    pub fn hostnossl(address: &str, method: HbaAuthMethod) -> HbaRule {
+        HbaRule::new(HbaConnectionType::HostNoSsl, Some(address), method)
+    }
+
+    fn new(
+        connection_type: HbaConnectionType,
+        address: Option<&str>,
+        method: HbaAuthMethod,
+    ) -> HbaRule {
+        HbaRule {
+            connection_type,
+            database: "all".into(),
+            user: "all".into(),
+            address: address.map(String::from),
+            method,
+        }
+    }
+
+    /// Only match connections to the given database(s), e.g. `mydb` or `mydb,otherdb`
+    #[must_use]
+    pub fn database(mut self, database: &str) -> Self {
+        self.database = database.into();
+        self
+    }
+
+    /// Only match connections as the given user(s), e.g. `app` or `+members_of_role`
+    #[must_use]
+    pub fn user(mut self, user: &str) -> Self {
+        self.user = user.into();
+        self
+    }
+
+    /// The rules used by [`PgTempDBBuilder::require_password_auth`](crate::PgTempDBBuilder::require_password_auth):
+    /// SCRAM-SHA-256 password authentication for all local connections.
+    pub fn password_auth_rules() -> Vec<HbaRule> {
+        vec![
+            HbaRule::local(HbaAuthMethod::ScramSha256),
+            HbaRule::host("127.0.0.1/32", HbaAuthMethod::ScramSha256),
+            HbaRule::host("::1/128", HbaAuthMethod::ScramSha256),
+        ]
+    }
+}
+
+impl fmt::Display for HbaConnectionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            HbaConnectionType::Local => "local",
+            HbaConnectionType::Host => "host",
+            HbaConnectionType::HostSsl => "hostssl",
+            HbaConnectionType::HostNoSsl => "hostnossl",
+        };
+        f.write_str(s)
+    }
+}
+
+impl fmt::Display for HbaAuthMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            HbaAuthMethod::Trust => "trust",
+            HbaAuthMethod::Reject => "reject",
+            HbaAuthMethod::ScramSha256 => "scram-sha-256",
+            HbaAuthMethod::Md5 => "md5",
+            HbaAuthMethod::Password => "password",
+            HbaAuthMethod::Cert => "cert",
+            HbaAuthMethod::Peer => "peer",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Formats the rule as a `pg_hba.conf` line
+impl fmt::Display for HbaRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.connection_type, self.database, self.user
+        )?;
+        if let Some(address) = &self.address {
+            write!(f, " {}", address)?;
+        }
+        write!(f, " {}", self.method)
+    }
+}
+
+/// The contents of a `pg_hba.conf` file with the given rules
+pub(crate) fn hba_file_contents(rules: &[HbaRule]) -> String {
+    let mut contents = String::from("# generated by pgtemp\n");
+    for rule in rules {
+        contents.push_str(&rule.to_string());
+        contents.push('\n');
+    }
+    contents
+}
@@ -0,0 +1,343 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::PgTempDBBuilder;
+
+/// The server encodings supported by PostgreSQL, see
+/// <https://www.postgresql.org/docs/current/multibyte.html#CHARSET-TABLE>
+const SERVER_ENCODINGS: &[&str] = &[
+    "BIG5",
+    "EUC_CN",
+    "EUC_JP",
+    "EUC_JIS_2004",
+    "EUC_KR",
+    "EUC_TW",
+    "ISO_8859_5",
+    "ISO_8859_6",
+    "ISO_8859_7",
+    "ISO_8859_8",
+    "KOI8R",
+    "KOI8U",
+    "LATIN1",
+    "LATIN2",
+    "LATIN3",
+    "LATIN4",
+    "LATIN5",
+    "LATIN6",
+    "LATIN7",
+    "LATIN8",
+    "LATIN9",
+    "LATIN10",
+    "MULE_INTERNAL",
+    "SQL_ASCII",
+    "UTF8",
+    "WIN866",
+    "WIN874",
+    "WIN1250",
+    "WIN1251",
+    "WIN1252",
+    "WIN1253",
+    "WIN1254",
+    "WIN1255",
+    "WIN1256",
+    "WIN1257",
+    "WIN1258",
+];
+
+/// Where zoneinfo files are typically installed. Postgres uses either the system's files or its
+/// own copy in its `share/timezone` directory.
+const ZONEINFO_DIRS: &[&str] = &[
+    "/usr/share/zoneinfo",
+    "/usr/lib/zoneinfo",
+    "/usr/share/lib/zoneinfo",
+];
+
+/// The library that provides the database's collations. See
+/// [`PgTempDBBuilder::with_icu_locale`](crate::PgTempDBBuilder::with_icu_locale).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum LocaleProvider {
+    /// The operating system's C library (the default)
+    Libc,
+    /// The ICU library
+    Icu,
+}
+
+/// The output format of the `DateStyle` setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum DateOutputStyle {
+    /// ISO 8601, e.g. `1997-12-17 07:37:16-08` (the default)
+    Iso,
+    /// SQL, e.g. `12/17/1997 07:37:16.00 PST`
+    Sql,
+    /// Traditional Postgres, e.g. `Wed Dec 17 07:37:16 1997 PST`
+    Postgres,
+    /// German, e.g. `17.12.1997 07:37:16.00 PST`
+    German,
+}
+
+/// The field order used by the `DateStyle` setting to output (for the SQL and Postgres styles)
+/// and interpret ambiguous dates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum DateOrder {
+    /// Day, month, year
+    Dmy,
+    /// Month, day, year (the default)
+    Mdy,
+    /// Year, month, day
+    Ymd,
+}
+
+/// The server's `DateStyle` setting. See
+/// [`PgTempDBBuilder::with_datestyle`](crate::PgTempDBBuilder::with_datestyle).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DateStyle {
+    /// The output format
+    pub output: DateOutputStyle,
+    /// The order of the day, month and year fields
+    pub order: DateOrder,
+}
+
+impl fmt::Display for LocaleProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            LocaleProvider::Libc => "libc",
+            LocaleProvider::Icu => "icu",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Formats the setting as it is passed to postgres, e.g. `ISO, MDY`
+impl fmt::Display for DateStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let output = match self.output {
+            DateOutputStyle::Iso => "ISO",
+            DateOutputStyle::Sql => "SQL",
+            DateOutputStyle::Postgres => "Postgres",
+            DateOutputStyle::German => "German",
+        };
+        let order = match self.order {
+            DateOrder::Dmy => "DMY",
+            DateOrder::Mdy => "MDY",
+            DateOrder::Ymd => "YMD",
+        };
+        write!(f, "{}, {}", output, order)
+    }
+}
+
+/// Check the builder's locale settings before running initdb, so that mistakes are reported
+/// clearly rather than as initdb or postgres failures.
+pub(crate) fn validate(builder: &PgTempDBBuilder) {
+    if let Some(encoding) = &builder.encoding {
+        let normalized = encoding.to_uppercase().replace('-', "_");
+        // initdb also accepts some aliases, e.g. `UTF-8`
+        let normalized = match normalized.as_str() {
+            "UTF_8" => "UTF8",
+            "ISO_8859_1" => "LATIN1",
+            other => other,
+        };
+        assert!(
+            SERVER_ENCODINGS.contains(&normalized),
+            "`{}` is not a PostgreSQL server encoding",
+            encoding
+        );
+    }
+
+    let libc_locales: Vec<&String> = [&builder.lc_collate, &builder.lc_ctype]
+        .into_iter()
+        .flatten()
+        .collect();
+    if !libc_locales.is_empty() {
+        let available = available_locales();
+        for locale in libc_locales {
+            assert!(
+                available.contains(&normalize_locale(locale)),
+                "locale `{}` is not available on this system (see `locale -a`)",
+                locale
+            );
+        }
+    }
+
+    if builder.icu_locale.is_some() || builder.icu_rules.is_some() {
+        assert!(
+            builder.locale_provider == Some(LocaleProvider::Icu),
+            "ICU locales and rules require the ICU locale provider"
+        );
+    }
+    if builder.locale_provider == Some(LocaleProvider::Icu) {
+        assert!(
+            builder.icu_locale.is_some(),
+            "the ICU locale provider requires an ICU locale"
+        );
+    }
+    if builder.icu_rules.is_some() {
+        let version = postgres_major_version(builder.bin_path.as_deref());
+        assert!(
+            version >= 16,
+            "ICU rules require PostgreSQL 16 or later, but initdb is version {}",
+            version
+        );
+    }
+
+    if let Some(timezone) = &builder.timezone {
+        validate_timezone(timezone, builder.bin_path.as_deref());
+    }
+}
+
+/// The initdb arguments for the builder's locale settings
+pub(crate) fn initdb_args(builder: &PgTempDBBuilder) -> Vec<(&'static str, String)> {
+    let mut args = Vec::new();
+    if let Some(encoding) = &builder.encoding {
+        args.push(("--encoding", encoding.clone()));
+    }
+    if let Some(locale) = &builder.lc_collate {
+        args.push(("--lc-collate", locale.clone()));
+    }
+    if let Some(locale) = &builder.lc_ctype {
+        args.push(("--lc-ctype", locale.clone()));
+    }
+    if let Some(provider) = builder.locale_provider {
+        args.push(("--locale-provider", provider.to_string()));
+    }
+    if let Some(locale) = &builder.icu_locale {
+        args.push(("--icu-locale", locale.clone()));
+    }
+    if let Some(rules) = &builder.icu_rules {
+        args.push(("--icu-rules", rules.clone()));
+    }
+    args
+}
+
+/// The server configuration parameters for the builder's time settings
+pub(crate) fn server_configs(builder: &PgTempDBBuilder) -> Vec<(&'static str, String)> {
+    let mut configs = Vec::new();
+    if let Some(timezone) = &builder.timezone {
+        configs.push(("TimeZone", timezone.clone()));
+    }
+    if let Some(datestyle) = builder.datestyle {
+        configs.push(("DateStyle", datestyle.to_string()));
+    }
+    configs
+}
+
+/// The locales listed by `locale -a`, normalized. `C` and `POSIX` are always available.
+fn available_locales() -> Vec<String> {
+    let output = Command::new("locale")
+        .arg("-a")
+        .output()
+        .expect("failed to run `locale -a` to check that the locale is available");
+    assert!(
+        output.status.success(),
+        "`locale -a` failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut locales: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(normalize_locale)
+        .collect();
+    locales.extend(["c".into(), "posix".into()]);
+    locales
+}
+
+/// `locale -a` lists e.g. `en_US.utf8` while `en_US.UTF-8` is more commonly used, so compare the
+/// codeset case-insensitively and ignoring dashes
+fn normalize_locale(locale: &str) -> String {
+    match locale.split_once('.') {
+        Some((name, codeset)) => format!("{}.{}", name, codeset.to_lowercase().replace('-', "")),
+        None => locale.to_string(),
+    }
+    .to_lowercase()
+}
+
+/// Check that a time zone name, e.g. `Europe/Berlin`, exists in the time zone database. Like
+/// postgres, the name is matched case-insensitively. Values that aren't names, like POSIX time
+/// zone specs (`<+03>-3`, `UTC+3`) and numeric offsets, are left for postgres to check.
+fn validate_timezone(timezone: &str, bin_path: Option<&Path>) {
+    let mut dirs: Vec<PathBuf> = ZONEINFO_DIRS.iter().map(PathBuf::from).collect();
+    if let Some(bin_path) = bin_path {
+        dirs.push(bin_path.join("../share/timezone"));
+    }
+    let dirs: Vec<PathBuf> = dirs.into_iter().filter(|dir| dir.is_dir()).collect();
+    // if we can't find the timezone database, let postgres check the name
+    if dirs.is_empty() {
+        return;
+    }
+
+    if dirs.iter().any(|dir| has_zone_file(dir, timezone)) {
+        return;
+    }
+    // POSIX specs always contain an offset (or a `<...>` quoted name with one)
+    let looks_like_spec = timezone.starts_with('<')
+        || timezone.chars().any(|c| c.is_ascii_digit())
+        || timezone.to_lowercase().starts_with("interval");
+    assert!(looks_like_spec, "`{}` is not a known time zone", timezone);
+}
+
+/// Whether `dir` contains a zone file called `name`, comparing each path component
+/// case-insensitively
+fn has_zone_file(dir: &Path, name: &str) -> bool {
+    let mut path = dir.to_path_buf();
+    for component in name.split('/') {
+        if component.is_empty() || component.starts_with('.') {
+            return false;
+        }
+        let Ok(entries) = path.read_dir() else {
+            return false;
+        };
+        let found = entries.flatten().find(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|entry_name| entry_name.eq_ignore_ascii_case(component))
+        });
+        match found {
+            Some(entry) => path = entry.path(),
+            None => return false,
+        }
+    }
+    path.is_file()
+}
+
+/// The major version of the postgres installation, from `initdb --version`
+fn postgres_major_version(bin_path: Option<&Path>) -> u32 {
+    let initdb_path = bin_path.map_or("initdb".into(), |p| p.join("initdb"));
+    let output = Command::new(initdb_path)
+        .arg("--version")
+        .output()
+        .expect("Failed to start initdb. Is it installed and on your path?");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // e.g. `initdb (PostgreSQL) 15.18 (Debian 15.18-0+deb12u1)`
+    stdout
+        .split_whitespace()
+        .nth(2)
+        .map(|version| {
+            version
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect::<String>()
+        })
+        .and_then(|major| major.parse().ok())
+        .expect(&format!(
+            "failed to parse initdb version `{}`",
+            stdout.trim()
+        ))
+}
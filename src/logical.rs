@@ -0,0 +1,181 @@
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::run_db::{self, quote_ident, quote_literal};
+use crate::PgTempDB;
+
+/// How long [`PgTempDB::wait_for_subscription_sync`] waits for the subscriptions to catch up
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+const SYNC_POLL_DELAY: Duration = Duration::from_millis(50);
+
+impl PgTempDB {
+    /// Create a publication of the given tables with `CREATE PUBLICATION`, or of all tables if
+    /// `tables` is empty. The table names are used as is, so they may be schema-qualified. The
+    /// server must have been started with
+    /// [`PgTempDBBuilder::with_logical_replication`](crate::PgTempDBBuilder::with_logical_replication).
+    pub fn create_publication(&self, name: &str, tables: &[&str]) {
+        let mut sql = format!("CREATE PUBLICATION {}", quote_ident(name));
+        if tables.is_empty() {
+            sql.push_str(" FOR ALL TABLES");
+        } else {
+            sql.push_str(" FOR TABLE ");
+            sql.push_str(&tables.join(", "));
+        }
+        self.execute(&sql);
+    }
+
+    /// Subscribe to `publication` on `publisher` with `CREATE SUBSCRIPTION`, which copies the
+    /// published tables' existing rows and then streams their changes. Published tables that
+    /// don't exist in this database yet are created first, from a `pg_dump` of their schema on
+    /// the publisher.
+    ///
+    /// The subscription is dropped before this server is shut down, along with its replication
+    /// slot on the publisher if the publisher is still running. Since `name` is also used as the
+    /// name of the replication slot, it must be unique among the publisher's subscribers.
+    pub fn create_subscription(&self, name: &str, publisher: &PgTempDB, publication: &str) {
+        self.create_published_tables(publisher, publication);
+
+        let sql = format!(
+            "CREATE SUBSCRIPTION {} CONNECTION {} PUBLICATION {}",
+            quote_ident(name),
+            quote_literal(&publisher.connection_uri()),
+            quote_ident(publication)
+        );
+        self.execute(&sql);
+        self.subscriptions
+            .lock()
+            .expect("subscriptions lock poisoned")
+            .push(name.into());
+    }
+
+    /// Block until this server's subscriptions to `publisher` have copied the initial contents of
+    /// their tables and applied all changes made on the publisher before this was called. Panics
+    /// if they don't catch up within 30 seconds.
+    pub fn wait_for_subscription_sync(&self, publisher: &PgTempDB) {
+        let start = Instant::now();
+        let tables_syncing =
+            "SELECT count(*) FROM pg_subscription_rel WHERE srsubstate NOT IN ('r', 's')";
+        wait_until(start, "initial table synchronization", || {
+            self.query(tables_syncing)[0][0].as_deref() == Some("0")
+        });
+
+        let subscriptions = self
+            .subscriptions
+            .lock()
+            .expect("subscriptions lock poisoned")
+            .clone();
+        if subscriptions.is_empty() {
+            return;
+        }
+        // see PgTempReplicaSet::wait_for_replay for why this is the insert position
+        let lsn = publisher.query("SELECT pg_current_wal_insert_lsn()")[0][0]
+            .clone()
+            .expect("pg_current_wal_insert_lsn returned NULL");
+        let slots: Vec<String> = subscriptions.iter().map(|s| quote_literal(s)).collect();
+        let slots_behind = format!(
+            "SELECT count(*) FROM pg_replication_slots WHERE slot_name IN ({}) \
+             AND (confirmed_flush_lsn IS NULL OR confirmed_flush_lsn < {}::pg_lsn)",
+            slots.join(", "),
+            quote_literal(&lsn)
+        );
+        wait_until(start, "subscriptions to catch up", || {
+            publisher.query(&slots_behind)[0][0].as_deref() == Some("0")
+        });
+    }
+
+    /// Create the tables published by `publication` that don't exist in this database yet
+    fn create_published_tables(&self, publisher: &PgTempDB, publication: &str) {
+        let tables = publisher.query(&format!(
+            "SELECT format('%I.%I', schemaname, tablename) FROM pg_publication_tables \
+             WHERE pubname = {}",
+            quote_literal(publication)
+        ));
+        let missing: Vec<String> = tables
+            .into_iter()
+            .filter_map(|row| row.into_iter().next().flatten())
+            .filter(|table| {
+                let sql = format!("SELECT to_regclass({})", quote_literal(table));
+                self.query(&sql)[0][0].is_none()
+            })
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+
+        let schema_file = tempfile::NamedTempFile::new().expect("failed to create temp file");
+        let pg_dump_path = self
+            .bin_path
+            .as_ref()
+            .map_or("pg_dump".into(), |p| p.join("pg_dump"));
+        let mut cmd = Command::new(pg_dump_path);
+        cmd.arg(publisher.connection_uri())
+            .arg("--schema-only")
+            .arg("--no-publications")
+            .arg("--no-subscriptions")
+            .arg("--file")
+            .arg(schema_file.path());
+        for table in &missing {
+            cmd.args(["--table", table]);
+        }
+        let output = cmd
+            .output()
+            .expect("failed to start pg_dump. Is it installed and on your path?");
+        if !output.status.success() {
+            let stdout = output.stdout;
+            let stderr = output.stderr;
+            panic!(
+                "pg_dump failed! stdout: {}\n\nstderr: {}",
+                String::from_utf8_lossy(&stdout),
+                String::from_utf8_lossy(&stderr)
+            );
+        }
+
+        self.load_database(schema_file.path());
+    }
+
+    /// Drop the subscriptions created with [`Self::create_subscription`], so that the publisher
+    /// doesn't keep their replication slots.
+    pub(crate) fn drop_subscriptions(&self) {
+        let subscriptions = std::mem::take(
+            &mut *self
+                .subscriptions
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner),
+        );
+        let conn_uri = self.unchecked_connection_info().uri();
+        for name in subscriptions {
+            let ident = quote_ident(&name);
+            let drop_sql = format!("DROP SUBSCRIPTION {}", ident);
+            if run_db::try_run_psql(self.bin_path.as_deref(), &conn_uri, &drop_sql).is_ok() {
+                continue;
+            }
+
+            // the publisher is gone, so the slot can't be dropped along with the subscription
+            let detach_sqls = [
+                format!("ALTER SUBSCRIPTION {} DISABLE", ident),
+                format!("ALTER SUBSCRIPTION {} SET (slot_name = NONE)", ident),
+                drop_sql,
+            ];
+            for sql in detach_sqls {
+                if let Err(e) = run_db::try_run_psql(self.bin_path.as_deref(), &conn_uri, &sql) {
+                    eprintln!("pgtemp: failed to drop subscription {}: {}", name, e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Poll `done` until it returns true, panicking if that takes longer than [`SYNC_TIMEOUT`] from
+/// `start`.
+fn wait_until(start: Instant, what: &str, mut done: impl FnMut() -> bool) {
+    while !done() {
+        assert!(
+            start.elapsed() <= SYNC_TIMEOUT,
+            "timed out after {:?} waiting for {}",
+            SYNC_TIMEOUT,
+            what
+        );
+        std::thread::sleep(SYNC_POLL_DELAY);
+    }
+}
@@ -1,9 +1,74 @@
 //! Main binary for pgtemp. It just reads the arguments via clap and passes them to
-//! `PgTempDaemon::from_args`
-use clap::Parser;
+//! `PgTempDaemon::from_args_with_builder` along with the config file, or runs the given
+//! subcommand
+use std::path::PathBuf;
+
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use pgtemp::PgTempDBBuilder;
+
+#[derive(Parser, Debug)]
+#[command(author, version)]
+#[command(args_conflicts_with_subcommands = true)]
+/// pgtemp allows you to spawn temporary postgresql databases for testing.
+/// You provide a connection URI and pgtemp will listen on the given port and proxy each
+/// connection to a new temporary database.
+/// When the connection is disconnected, the database is cleaned up.
+struct Cli {
+    #[command(subcommand)]
+    /// Run a command instead of the daemon
+    command: Option<Command>,
+
+    #[arg(long, value_name = "FILE")]
+    /// A pgtemp.toml configuration file. If not given, a pgtemp.toml file in the current
+    /// directory or one of its parents is used if there is one.
+    config: Option<PathBuf>,
+
+    #[command(flatten)]
+    daemon: Option<pgtemp::PgTempDaemonArgs>,
+}
+
+#[derive(Subcommand, Debug)]
+/// Commands other than running the daemon
+enum Command {
+    /// Stop the servers of processes that were killed before they could shut them down, and
+    /// delete their data directories.
+    Gc {
+        #[arg(long, value_name = "DIR")]
+        /// The directory to clean up. Default: the directories pgtemp uses by default.
+        data_dir_prefix: Option<PathBuf>,
+    },
+}
 
 #[tokio::main]
 async fn main() {
-    let args = pgtemp::PgTempDaemonArgs::parse();
-    pgtemp::PgTempDaemon::from_args(args).await.start().await;
+    let cli = Cli::parse();
+    match (cli.command, cli.daemon) {
+        (Some(Command::Gc { data_dir_prefix }), _) => {
+            let collected = match data_dir_prefix {
+                Some(dir) => pgtemp::gc_in(dir),
+                None => pgtemp::gc(),
+            };
+            for temp_dir in &collected {
+                println!("cleaned up {}", temp_dir.display());
+            }
+            println!("cleaned up {} orphaned pgtemp servers", collected.len());
+        }
+        (None, Some(args)) => {
+            let builder = match cli.config {
+                Some(config) => PgTempDBBuilder::from_config_file(config),
+                None => PgTempDBBuilder::from_discovered_config_file(),
+            };
+            pgtemp::PgTempDaemon::from_args_with_builder(args, builder)
+                .await
+                .start()
+                .await;
+        }
+        (None, None) => Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "a connection URI is required to start the daemon",
+            )
+            .exit(),
+    }
 }
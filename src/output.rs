@@ -0,0 +1,65 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::process::Child;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The number of lines of server output kept for crash reports and error messages
+pub(crate) const OUTPUT_TAIL_LINES: usize = 30;
+/// How long to wait for the last of the output once the server has exited
+const EXIT_OUTPUT_TIMEOUT: Duration = Duration::from_secs(1);
+const EXIT_OUTPUT_POLL_DELAY: Duration = Duration::from_millis(10);
+
+/// The last lines of a server's stderr, if it isn't written to a log file.
+///
+/// The pipe is read continuously by a background thread: if nothing read it, the server would
+/// block as soon as the pipe's buffer is full, e.g. after a few hundred statements with
+/// `log_statement = all`.
+#[derive(Clone)]
+pub(crate) struct ServerOutput {
+    state: Arc<OutputState>,
+}
+
+#[derive(Default)]
+struct OutputState {
+    lines: Mutex<VecDeque<String>>,
+    /// set once the pipe is closed, i.e. the server and all its children have exited
+    closed: AtomicBool,
+}
+
+impl ServerOutput {
+    /// Start reading the process's stderr, if it is piped
+    pub(crate) fn capture(process: &mut Child) -> Option<ServerOutput> {
+        let stderr = process.stderr.take()?;
+        let state = Arc::new(OutputState::default());
+
+        let thread_state = Arc::clone(&state);
+        let _handle = std::thread::spawn(move || {
+            // split rather than `lines` so that invalid UTF-8 doesn't stop the reading
+            for line in BufReader::new(stderr).split(b'\n') {
+                let Ok(line) = line else {
+                    break;
+                };
+                let mut lines = thread_state.lines.lock().unwrap();
+                if lines.len() == OUTPUT_TAIL_LINES {
+                    let _line = lines.pop_front();
+                }
+                lines.push_back(String::from_utf8_lossy(&line).into_owned());
+            }
+            thread_state.closed.store(true, Ordering::SeqCst);
+        });
+
+        Some(ServerOutput { state })
+    }
+
+    /// The last lines of output of a server that has exited, once they have been read
+    pub(crate) fn tail_after_exit(&self) -> String {
+        let start = Instant::now();
+        while !self.state.closed.load(Ordering::SeqCst) && start.elapsed() < EXIT_OUTPUT_TIMEOUT {
+            std::thread::sleep(EXIT_OUTPUT_POLL_DELAY);
+        }
+        let lines = self.state.lines.lock().unwrap();
+        lines.iter().cloned().collect::<Vec<_>>().join("\n")
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A preset of server configuration parameters, applied underneath the builder's
+/// `server_configs` so that individual parameters can still be overridden. See
+/// [`PgTempDBBuilder::with_server_profile`](crate::PgTempDBBuilder::with_server_profile).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ServerProfile {
+    /// Turn off durability and autovacuum for speed (the default). Data may be lost if the server
+    /// crashes. See the [PostgreSQL
+    /// docs](https://www.postgresql.org/docs/current/non-durability.html).
+    #[default]
+    Fast,
+    /// Durable writes and autovacuum, as on a production server, e.g. for testing crash recovery
+    /// or behaviour that depends on autovacuum
+    ProductionLike,
+    /// Like `Fast`, with tiny `shared_buffers` and `max_connections` so that hundreds of servers
+    /// can run at once
+    Minimal,
+    /// Like `Fast`, with every statement, connection and lock wait logged. Use with
+    /// [`PgTempDBBuilder::with_log_file`](crate::PgTempDBBuilder::with_log_file) to keep the logs.
+    Debug,
+    /// No presets: the server uses postgres's own defaults, and only `server_configs` is applied
+    PostgresDefaults,
+}
+
+// https://wiki.postgresql.org/wiki/Tuning_Your_PostgreSQL_Server
+const FAST_CONFIGS: &[(&str, &str)] = &[
+    ("fsync", "off"),
+    ("synchronous_commit", "off"),
+    ("full_page_writes", "off"),
+    ("autovacuum", "off"),
+];
+
+const PRODUCTION_LIKE_CONFIGS: &[(&str, &str)] = &[
+    ("fsync", "on"),
+    ("synchronous_commit", "on"),
+    ("full_page_writes", "on"),
+    ("autovacuum", "on"),
+];
+
+const MINIMAL_CONFIGS: &[(&str, &str)] = &[
+    ("shared_buffers", "1MB"),
+    ("max_connections", "10"),
+    ("max_worker_processes", "2"),
+    ("max_parallel_workers", "0"),
+];
+
+const DEBUG_CONFIGS: &[(&str, &str)] = &[
+    ("log_statement", "all"),
+    ("log_connections", "on"),
+    ("log_disconnections", "on"),
+    ("log_lock_waits", "on"),
+    ("log_min_messages", "debug1"),
+    ("log_line_prefix", "%m [%p] %q%u@%d "),
+];
+
+impl ServerProfile {
+    /// The server configuration parameters set by this profile
+    pub fn server_configs(self) -> Vec<(&'static str, &'static str)> {
+        let presets: &[&[(&str, &str)]] = match self {
+            ServerProfile::Fast => &[FAST_CONFIGS],
+            ServerProfile::ProductionLike => &[PRODUCTION_LIKE_CONFIGS],
+            ServerProfile::Minimal => &[FAST_CONFIGS, MINIMAL_CONFIGS],
+            ServerProfile::Debug => &[FAST_CONFIGS, DEBUG_CONFIGS],
+            ServerProfile::PostgresDefaults => &[],
+        };
+        presets
+            .iter()
+            .flat_map(|configs| configs.iter().copied())
+            .collect()
+    }
+}
+
+impl fmt::Display for ServerProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ServerProfile::Fast => "fast",
+            ServerProfile::ProductionLike => "production_like",
+            ServerProfile::Minimal => "minimal",
+            ServerProfile::Debug => "debug",
+            ServerProfile::PostgresDefaults => "postgres_defaults",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Parses the names used by [`Display`](fmt::Display), e.g. `production_like`. Dashes may be used
+/// instead of underscores.
+impl FromStr for ServerProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "fast" => Ok(ServerProfile::Fast),
+            "production_like" => Ok(ServerProfile::ProductionLike),
+            "minimal" => Ok(ServerProfile::Minimal),
+            "debug" => Ok(ServerProfile::Debug),
+            "postgres_defaults" => Ok(ServerProfile::PostgresDefaults),
+            _ => Err(format!("unknown server profile `{}`", s)),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt;
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+
+use crate::run_db::quote_literal;
+use crate::{HbaRule, PgTempDB, PgTempDBBuilder};
+
+/// How long [`PgTempReplicaSet::wait_for_replay`] waits for the replicas to catch up
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(30);
+const REPLAY_POLL_DELAY: Duration = Duration::from_millis(20);
+
+/// A primary PostgreSQL server with hot-standby replicas that stream its WAL, for testing
+/// read-replica routing, replication lag and failover. Each node is a [`PgTempDB`] with its own
+/// temp dir and port, so [`PgTempDB::connection_uri`] gives its connection details.
+///
+/// The replicas are created from a `pg_basebackup` of the primary and started with the same
+/// builder settings, on a random port. They are read-only, and the builder's startup steps (roles,
+/// databases, extensions, scripts and migrations) are only run on the primary, from which they are
+/// replicated.
+///
+/// If the builder has `hba_rules`, each rule for `all` databases is repeated for the
+/// `replication` pseudo-database so that the replicas can connect to the primary.
+///
+/// The replicas are shut down before the primary when the set is dropped.
+pub struct PgTempReplicaSet {
+    // declared before `primary` so that they are dropped first
+    replicas: Vec<PgTempDB>,
+    primary: PgTempDB,
+    replica_builder: PgTempDBBuilder,
+}
+
+impl PgTempReplicaSet {
+    /// Start a primary with the parameters configured from a PgTempDBBuilder, and `replicas`
+    /// replicas of it
+    pub fn from_builder(mut builder: PgTempDBBuilder, replicas: usize) -> PgTempReplicaSet {
+        let wal_level_set = builder
+            .server_configs
+            .keys()
+            .any(|k| k.eq_ignore_ascii_case("wal_level"));
+        if !wal_level_set {
+            builder
+                .server_configs
+                .insert("wal_level".into(), "replica".into());
+        }
+        let replication_rules: Vec<HbaRule> = builder
+            .hba_rules
+            .iter()
+            .filter(|rule| rule.database == "all")
+            .map(|rule| rule.clone().database("replication"))
+            .collect();
+        builder.hba_rules.extend(replication_rules);
+
+        // the replicas get their own ports, and only the primary dumps the database
+        let mut replica_builder = builder.clone();
+        replica_builder.port = None;
+        replica_builder.dump_path = None;
+
+        let primary = PgTempDB::from_builder(builder);
+        let mut set = PgTempReplicaSet {
+            replicas: Vec::new(),
+            primary,
+            replica_builder,
+        };
+        for _ in 0..replicas {
+            set.add_replica();
+        }
+        set
+    }
+
+    /// Start another replica of the primary and return it
+    pub fn add_replica(&mut self) -> &PgTempDB {
+        let replica =
+            PgTempDB::start_standby(self.replica_builder.clone(), &self.primary.connection_uri());
+        self.replicas.push(replica);
+        self.replicas.last().unwrap()
+    }
+
+    /// Returns the primary server
+    pub fn primary(&self) -> &PgTempDB {
+        &self.primary
+    }
+
+    /// Returns the replicas, in the order they were started
+    pub fn replicas(&self) -> &[PgTempDB] {
+        &self.replicas
+    }
+
+    /// Returns the replica at `index`. Panics if there is no such replica.
+    pub fn replica(&self, index: usize) -> &PgTempDB {
+        self.replicas.get(index).unwrap_or_else(|| {
+            panic!(
+                "no replica {}: the set has {} replicas",
+                index,
+                self.replicas.len()
+            )
+        })
+    }
+
+    /// Block until every replica has replayed all of the WAL that the primary had written when
+    /// this was called, so that changes committed on the primary beforehand are visible on the
+    /// replicas. Panics if they don't catch up within 30 seconds.
+    pub fn wait_for_replay(&self) {
+        // the insert position rather than the write position, which may not include commits yet
+        // when `synchronous_commit` is off, as it is with the default server profile
+        let lsn = self.primary.query("SELECT pg_current_wal_insert_lsn()")[0][0]
+            .clone()
+            .expect("pg_current_wal_insert_lsn returned NULL");
+        let caught_up_sql = format!(
+            "SELECT pg_last_wal_replay_lsn() >= {}::pg_lsn",
+            quote_literal(&lsn)
+        );
+
+        for (index, replica) in self.replicas.iter().enumerate() {
+            let start = Instant::now();
+            while replica.query(&caught_up_sql)[0][0].as_deref() != Some("t") {
+                if start.elapsed() > REPLAY_TIMEOUT {
+                    let replayed = replica.query("SELECT pg_last_wal_replay_lsn()")[0][0].clone();
+                    panic!(
+                        "replica {} did not replay the primary's WAL up to {} within {:?}: \
+                         replayed up to {}",
+                        index,
+                        lsn,
+                        REPLAY_TIMEOUT,
+                        replayed.as_deref().unwrap_or("(nothing)")
+                    );
+                }
+                std::thread::sleep(REPLAY_POLL_DELAY);
+            }
+        }
+    }
+
+    /// Fail over to the replica at `index`: the primary is shut down (after sending its remaining
+    /// WAL to the replicas), the replica is promoted and becomes the set's primary, and the other
+    /// replicas are reconfigured to stream from it. Note that the replicas' indexes after `index`
+    /// shift down by one.
+    ///
+    /// Reconfiguring the other replicas requires PostgreSQL 13 or later.
+    pub fn promote(&mut self, index: usize) {
+        assert!(
+            index < self.replicas.len(),
+            "no replica {}: the set has {} replicas",
+            index,
+            self.replicas.len()
+        );
+        let new_primary = self.replicas.remove(index);
+        let old_primary = std::mem::replace(&mut self.primary, new_primary);
+        old_primary.shutdown();
+
+        let promoted = self.primary.query("SELECT pg_promote()");
+        assert!(
+            promoted[0][0].as_deref() == Some("t"),
+            "failed to promote replica {} within 60 seconds",
+            index
+        );
+
+        let primary_conninfo = format!(
+            "ALTER SYSTEM SET primary_conninfo = {}",
+            quote_literal(&self.primary.connection_uri())
+        );
+        for replica in &self.replicas {
+            // ALTER SYSTEM can't be run in the same transaction as anything else
+            replica.execute(&primary_conninfo);
+            replica.execute("SELECT pg_reload_conf()");
+        }
+    }
+
+    /// Shut down the replicas and then the primary, and delete their data directories.
+    /// Equivalent to calling drop on this struct. See [`PgTempDB::shutdown`].
+    pub fn shutdown(self) {
+        drop(self);
+    }
+}
+
+impl Debug for PgTempReplicaSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PgTempReplicaSet")
+            .field("primary", &self.primary)
+            .field("replicas", &self.replicas)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,96 @@
+use crate::run_db;
+
+/// An attribute of a role created with
+/// [`PgTempDBBuilder::with_role`](crate::PgTempDBBuilder::with_role). See the [`CREATE ROLE`
+/// docs](https://www.postgresql.org/docs/current/sql-createrole.html) for details.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum RoleAttribute {
+    /// `LOGIN`: the role can log in. This is the default unless `NoLogin` is given, since roles
+    /// have a password.
+    Login,
+    /// `NOLOGIN`: the role can't log in, e.g. a group role
+    NoLogin,
+    /// `SUPERUSER`
+    Superuser,
+    /// `NOSUPERUSER` (the default)
+    NoSuperuser,
+    /// `CREATEDB`: the role can create databases
+    CreateDb,
+    /// `NOCREATEDB` (the default)
+    NoCreateDb,
+    /// `CREATEROLE`: the role can create other roles
+    CreateRole,
+    /// `NOCREATEROLE` (the default)
+    NoCreateRole,
+    /// `REPLICATION`: the role can start replication
+    Replication,
+    /// `BYPASSRLS`: the role bypasses row-level security policies
+    BypassRls,
+    /// `IN ROLE`: the role is a member of the given role, which must already exist
+    InRole(String),
+}
+
+/// A role created on startup. See [`PgTempDBBuilder::with_role`](crate::PgTempDBBuilder::with_role).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Role {
+    /// The role's name
+    pub name: String,
+    /// The role's password
+    pub password: String,
+    /// The role's attributes
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub attributes: Vec<RoleAttribute>,
+}
+
+impl Role {
+    /// The `CREATE ROLE` statement that creates this role
+    pub(crate) fn create_sql(&self) -> String {
+        let mut options = Vec::new();
+        let login_set = self
+            .attributes
+            .iter()
+            .any(|a| matches!(a, RoleAttribute::Login | RoleAttribute::NoLogin));
+        if !login_set {
+            options.push("LOGIN".to_string());
+        }
+        let mut in_roles = Vec::new();
+        for attribute in &self.attributes {
+            let option = match attribute {
+                RoleAttribute::Login => "LOGIN",
+                RoleAttribute::NoLogin => "NOLOGIN",
+                RoleAttribute::Superuser => "SUPERUSER",
+                RoleAttribute::NoSuperuser => "NOSUPERUSER",
+                RoleAttribute::CreateDb => "CREATEDB",
+                RoleAttribute::NoCreateDb => "NOCREATEDB",
+                RoleAttribute::CreateRole => "CREATEROLE",
+                RoleAttribute::NoCreateRole => "NOCREATEROLE",
+                RoleAttribute::Replication => "REPLICATION",
+                RoleAttribute::BypassRls => "BYPASSRLS",
+                RoleAttribute::InRole(role) => {
+                    in_roles.push(run_db::quote_ident(role));
+                    continue;
+                }
+            };
+            options.push(option.to_string());
+        }
+        options.push(format!(
+            "PASSWORD {}",
+            run_db::quote_literal(&self.password)
+        ));
+        if !in_roles.is_empty() {
+            options.push(format!("IN ROLE {}", in_roles.join(", ")));
+        }
+
+        format!(
+            "CREATE ROLE {} WITH {}",
+            run_db::quote_ident(&self.name),
+            options.join(" ")
+        )
+    }
+}
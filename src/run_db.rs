@@ -1,22 +1,59 @@
 use std::{
+    path::Path,
     process::{Child, Command},
     time::Duration,
 };
 use tempfile::TempDir;
 
+use crate::hba;
+use crate::locale;
+use crate::storage;
 use crate::PgTempDBBuilder;
 
 const CREATEDB_MAX_TRIES: u32 = 10;
 const CREATEDB_RETRY_DELAY: Duration = Duration::from_millis(100);
+const STOP_POLL_DELAY: Duration = Duration::from_millis(50);
+/// how long to wait after SIGINT before giving up and sending SIGKILL
+const STOP_TIMEOUT: Duration = Duration::from_secs(30);
+/// how long to wait after SIGKILL before giving up on a process that doesn't go away, e.g. a
+/// zombie that isn't our child
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+/// how long to wait for a server that has to recover before accepting connections, e.g. a standby
+const START_TIMEOUT: Duration = Duration::from_secs(30);
 
-fn current_user_is_root() -> bool {
+pub fn current_user_is_root() -> bool {
     unsafe { libc::getuid() == 0 }
 }
 
-/// Execute the `initdb` binary with the parameters configured in PgTempDBBuilder.
-pub fn init_db(builder: &mut PgTempDBBuilder) -> TempDir {
+/// Recursively change the owner of `path` to the `postgres` user.
+pub fn chown_to_postgres(path: &Path) {
+    // TODO: don't shell out to chown, get the userid of postgres and just call std::os
+    let chown_output = Command::new("chown")
+        .args(["-R", "postgres", path.to_str().unwrap()])
+        .output()
+        .expect(&format!("failed to chown {:?} to postgres user", path));
+    if !chown_output.status.success() {
+        let stdout = chown_output.stdout;
+        let stderr = chown_output.stderr;
+        panic!(
+            "chowning {:?} failed! stdout: {}\n\nstderr: {}",
+            path,
+            String::from_utf8_lossy(&stdout),
+            String::from_utf8_lossy(&stderr)
+        );
+    }
+}
+
+/// Create the temp dir that the data directory is put in.
+pub fn create_temp_dir(builder: &PgTempDBBuilder) -> TempDir {
+    let base_dir = builder.temp_dir_prefix.clone().or_else(|| {
+        builder
+            .memory_backed_storage
+            .then(storage::memory_backed_dir)
+            .flatten()
+    });
     let temp_dir = {
-        if let Some(base_dir) = builder.temp_dir_prefix.clone() {
+        if let Some(base_dir) = base_dir {
             TempDir::with_prefix_in("pgtemp-", base_dir).expect("failed to create tempdir")
         } else {
             TempDir::with_prefix("pgtemp-").expect("failed to create tempdir")
@@ -25,22 +62,16 @@ pub fn init_db(builder: &mut PgTempDBBuilder) -> TempDir {
 
     // if current user is root, data dir etc need to be owned by postgres user
     if current_user_is_root() {
-        // TODO: don't shell out to chown, get the userid of postgres and just call std::os
-        let chown_output = Command::new("chown")
-            .args(["-R", "postgres", temp_dir.path().to_str().unwrap()])
-            .output()
-            .expect("failed to chown data dir to postgres user");
-        if !chown_output.status.success() {
-            let stdout = chown_output.stdout;
-            let stderr = chown_output.stderr;
-            panic!(
-                "chowning data dir failed! stdout: {}\n\nstderr: {}",
-                String::from_utf8_lossy(&stdout),
-                String::from_utf8_lossy(&stderr)
-            );
-        }
+        chown_to_postgres(temp_dir.path());
     }
 
+    temp_dir
+}
+
+/// Execute the `initdb` binary with the parameters configured in PgTempDBBuilder.
+pub fn init_db(builder: &mut PgTempDBBuilder) -> TempDir {
+    let temp_dir = create_temp_dir(builder);
+
     let data_dir = temp_dir.path().join("pg_data_dir");
     let data_dir_str = data_dir.to_str().unwrap();
 
@@ -82,6 +113,9 @@ pub fn init_db(builder: &mut PgTempDBBuilder) -> TempDir {
         };
         cmd.args([formatted_key.as_str(), val]);
     }
+    for (arg, val) in locale::initdb_args(builder) {
+        cmd.args([arg, &val]);
+    }
 
     // TODO: supply postgres install location in builder struct
     let initdb_output = cmd
@@ -101,11 +135,68 @@ pub fn init_db(builder: &mut PgTempDBBuilder) -> TempDir {
     temp_dir
 }
 
-pub fn run_db(temp_dir: &TempDir, mut builder: PgTempDBBuilder) -> Child {
+/// Copy the data directory of the server at `primary_uri` into a new temp dir with
+/// `pg_basebackup`, configured to run as a standby that streams WAL from it.
+pub fn standby_base_backup(builder: &PgTempDBBuilder, primary_uri: &str) -> TempDir {
+    let temp_dir = create_temp_dir(builder);
+    let data_dir = temp_dir.path().join("pg_data_dir");
+    base_backup(builder.bin_path.as_deref(), primary_uri, &data_dir, true);
+    temp_dir
+}
+
+/// Copy the data directory of the server at `conn_uri` to `data_dir` with `pg_basebackup`. If
+/// `standby` is true, the copy is configured to run as a standby that streams WAL from the server.
+pub fn base_backup(bin_path: Option<&Path>, conn_uri: &str, data_dir: &Path, standby: bool) {
+    let pg_basebackup_path = bin_path.map_or("pg_basebackup".into(), |p| p.join("pg_basebackup"));
+
+    // the data dir has to be owned by the postgres user if we are root, as with initdb
+    let mut cmd: Command;
+    if current_user_is_root() {
+        cmd = Command::new("sudo");
+        cmd.args(["-u", "postgres"]).arg(pg_basebackup_path);
+    } else {
+        cmd = Command::new(pg_basebackup_path);
+    }
+
+    cmd.args(["--dbname", conn_uri])
+        .args(["--pgdata", data_dir.to_str().unwrap()])
+        .args(["--wal-method", "stream"])
+        .args(["--checkpoint", "fast"])
+        .arg("--no-sync");
+    if standby {
+        // writes standby.signal and primary_conninfo
+        cmd.arg("--write-recovery-conf");
+    }
+
+    let output = cmd
+        .output()
+        .expect("Failed to start pg_basebackup. Is it installed and on your path?");
+
+    if !output.status.success() {
+        let stdout = output.stdout;
+        let stderr = output.stderr;
+        panic!(
+            "pg_basebackup failed! stdout: {}\n\nstderr: {}",
+            String::from_utf8_lossy(&stdout),
+            String::from_utf8_lossy(&stderr)
+        );
+    }
+}
+
+/// Start the postgres server in `temp_dir`. The database named in the builder is created unless
+/// `create_database` is false, e.g. for a standby, which gets its databases from the primary.
+pub fn run_db(temp_dir: &TempDir, mut builder: PgTempDBBuilder, create_database: bool) -> Child {
     let data_dir = temp_dir.path().join("pg_data_dir");
     let data_dir_str = data_dir.to_str().unwrap();
     let port = builder.get_port_or_set_random();
 
+    if !builder.hba_rules.is_empty() {
+        // overwrite the existing file so that it keeps its owner when we're running as root
+        let hba_path = data_dir.join("pg_hba.conf");
+        std::fs::write(&hba_path, hba::hba_file_contents(&builder.hba_rules))
+            .expect(&format!("failed to write {:?}", hba_path));
+    }
+
     // postgres will not run as root, so try to run as postgres if we are root
     let postgres_path = builder
         .bin_path
@@ -122,21 +213,41 @@ pub fn run_db(temp_dir: &TempDir, mut builder: PgTempDBBuilder) -> Child {
     pgcmd
         .args(["-c", &format!("unix_socket_directories={}", data_dir_str)])
         .args(["-c", &format!("port={port}")])
-        // https://www.postgresql.org/docs/current/non-durability.html
-        // https://wiki.postgresql.org/wiki/Tuning_Your_PostgreSQL_Server
-        .args(["-c", "fsync=off"])
-        .args(["-c", "synchronous_commit=off"])
-        .args(["-c", "full_page_writes=off"])
-        .args(["-c", "autovacuum=off"])
         .args(["-D", data_dir.to_str().unwrap()]);
+    // the profile's presets are overridden by any explicitly configured parameters
+    for (key, val) in builder.server_profile.server_configs() {
+        let overridden = builder
+            .server_configs
+            .keys()
+            .any(|k| k.eq_ignore_ascii_case(key));
+        if !overridden {
+            pgcmd.args(["-c", &format!("{}={}", key, val)]);
+        }
+    }
     for (key, val) in &builder.server_configs {
         pgcmd.args(["-c", &format!("{}={}", key, val)]);
     }
+    for (key, val) in locale::server_configs(&builder) {
+        pgcmd.args(["-c", &format!("{}={}", key, val)]);
+    }
 
-    // don't output postgres output to stdout/stderr
-    pgcmd
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped());
+    if let Some(log_path) = &builder.log_path {
+        let log_file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path)
+            .expect(&format!("failed to open postgres log file {:?}", log_path));
+        let log_file_stderr = log_file
+            .try_clone()
+            .expect("failed to clone postgres log file handle");
+        pgcmd.stdout(log_file).stderr(log_file_stderr);
+    } else {
+        // don't output postgres output to stdout/stderr. Postgres logs to stderr, which is read
+        // by `ServerOutput`; nothing would read stdout, so discard it rather than let it fill up
+        pgcmd
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped());
+    }
 
     let postgres_server_process = pgcmd
         .spawn()
@@ -145,11 +256,11 @@ pub fn run_db(temp_dir: &TempDir, mut builder: PgTempDBBuilder) -> Child {
     std::thread::sleep(CREATEDB_RETRY_DELAY);
 
     let user = builder.get_user();
-    //let password = builder.get_password();
+    let password = builder.get_password();
     let port = builder.get_port_or_set_random();
     let dbname = builder.get_dbname();
 
-    if dbname != "postgres" {
+    if create_database && dbname != "postgres" {
         // TODO: don't use createdb, connect directly to the db and run CREATE DATABASE. removes
         // dependency on OS package which is often separate from postgres server package (at
         // expense of adding Cargo dependency)
@@ -169,9 +280,8 @@ pub fn run_db(temp_dir: &TempDir, mut builder: PgTempDBBuilder) -> Child {
                 .args(["--username", &user])
                 // TODO: use template in pgtemp daemon single-cluster mode?
                 //.args(["--template", "..."]
-                // TODO: since we trust local users by default we don't actually
-                // need the password but we should provide it anyway since we
-                // provide it everywhere else
+                // needed if password authentication is required by the hba rules
+                .env("PGPASSWORD", &password)
                 .arg("--no-password")
                 .arg(&dbname);
 
@@ -197,3 +307,208 @@ pub fn run_db(temp_dir: &TempDir, mut builder: PgTempDBBuilder) -> Child {
 
     postgres_server_process
 }
+
+/// Wait until the server at `conn_uri` accepts connections, panicking with the last error if it
+/// doesn't within [`START_TIMEOUT`].
+pub fn wait_for_server(bin_path: Option<&Path>, conn_uri: &str) {
+    let start = std::time::Instant::now();
+    loop {
+        match try_run_psql(bin_path, conn_uri, "SELECT 1") {
+            Ok(_) => return,
+            Err(e) if start.elapsed() > START_TIMEOUT => {
+                panic!(
+                    "server did not accept connections within {:?}: {}",
+                    START_TIMEOUT, e
+                )
+            }
+            Err(_) => std::thread::sleep(CREATEDB_RETRY_DELAY),
+        }
+    }
+}
+
+/// Run the given SQL with `psql` against the database at `conn_uri` and return its stdout,
+/// panicking with psql's output if it fails.
+pub fn run_psql(bin_path: Option<&Path>, conn_uri: &str, sql: &str) -> String {
+    try_run_psql(bin_path, conn_uri, sql).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Like [`run_psql`], but returns psql's output as an error instead of panicking.
+pub fn try_run_psql(bin_path: Option<&Path>, conn_uri: &str, sql: &str) -> Result<String, String> {
+    let mut cmd = psql_command(bin_path, conn_uri, sql);
+    cmd.arg("--quiet");
+    let stdout = psql_output(cmd)?;
+    Ok(String::from_utf8_lossy(&stdout).into_owned())
+}
+
+/// Run the given query with `psql` and return the rows of its result, with each value in
+/// postgres's text format and `None` for NULLs. Panics with psql's output if it fails.
+pub fn run_psql_query(
+    bin_path: Option<&Path>,
+    conn_uri: &str,
+    sql: &str,
+) -> Vec<Vec<Option<String>>> {
+    // COPY's text format escapes tabs, newlines and backslashes in values and writes NULLs as
+    // `\N`, unlike psql's own output formats, so the values can be recovered exactly
+    let stdout = run_psql_copy(bin_path, conn_uri, sql, "");
+    String::from_utf8_lossy(&stdout)
+        .lines()
+        .map(|line| line.split('\t').map(decode_copy_text_value).collect())
+        .collect()
+}
+
+/// Run the given query with `psql` and return its result as CSV, with a header row. Panics with
+/// psql's output if it fails.
+pub fn run_psql_csv(bin_path: Option<&Path>, conn_uri: &str, sql: &str) -> String {
+    let stdout = run_psql_copy(bin_path, conn_uri, sql, " WITH (FORMAT csv, HEADER)");
+    String::from_utf8_lossy(&stdout).into_owned()
+}
+
+/// Run `COPY (sql) TO STDOUT` with the given options and return the data.
+fn run_psql_copy(bin_path: Option<&Path>, conn_uri: &str, sql: &str, options: &str) -> Vec<u8> {
+    // the newline ends any trailing comment in the query
+    let sql = format!(
+        "COPY (\n{}\n) TO STDOUT{}",
+        sql.trim().trim_end_matches(';'),
+        options
+    );
+    let mut cmd = psql_command(bin_path, conn_uri, &sql);
+    cmd.arg("--quiet").env("PGCLIENTENCODING", "UTF8");
+    psql_output(cmd).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Decode a value in COPY's text format. See
+/// <https://www.postgresql.org/docs/current/sql-copy.html#id-1.9.3.55.9.2>
+fn decode_copy_text_value(value: &str) -> Option<String> {
+    if value == "\\N" {
+        return None;
+    }
+
+    let mut decoded = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('b') => decoded.push('\u{8}'),
+            Some('f') => decoded.push('\u{c}'),
+            Some('n') => decoded.push('\n'),
+            Some('r') => decoded.push('\r'),
+            Some('t') => decoded.push('\t'),
+            Some('v') => decoded.push('\u{b}'),
+            Some(other) => decoded.push(other),
+            None => decoded.push('\\'),
+        }
+    }
+    Some(decoded)
+}
+
+fn psql_command(bin_path: Option<&Path>, conn_uri: &str, sql: &str) -> Command {
+    let psql_path = bin_path.map_or("psql".into(), |p| p.join("psql"));
+
+    let mut cmd = Command::new(psql_path);
+    cmd.arg(conn_uri)
+        .args(["--command", sql])
+        .args(["--set", "ON_ERROR_STOP=1"])
+        .arg("--no-psqlrc");
+    cmd
+}
+
+/// Run psql and return its stdout, or its output as an error if it fails.
+fn psql_output(mut cmd: Command) -> Result<Vec<u8>, String> {
+    let output = cmd.output().map_err(|e| {
+        format!(
+            "failed to start psql. Is it installed and on your path? {}",
+            e
+        )
+    })?;
+
+    if !output.status.success() {
+        let stdout = output.stdout;
+        let stderr = output.stderr;
+        return Err(format!(
+            "psql failed! stdout: {}\n\nstderr: {}",
+            String::from_utf8_lossy(&stdout),
+            String::from_utf8_lossy(&stderr)
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Quote a SQL identifier (e.g. a database or role name) so that it can be safely interpolated
+/// into a query.
+pub fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Quote a SQL string literal (e.g. a password) so that it can be safely interpolated into a
+/// query. Assumes `standard_conforming_strings` is on, which is the default.
+pub fn quote_literal(literal: &str) -> String {
+    format!("'{}'", literal.replace('\'', "''"))
+}
+
+/// Returns whether a process with the given pid exists.
+pub fn process_is_running(pid: u32) -> bool {
+    #[allow(clippy::cast_possible_wrap)]
+    let ret = unsafe { libc::kill(pid as i32, 0) };
+    // EPERM means the process exists but belongs to another user, e.g. postgres when we're root
+    ret == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Returns the pid of the postmaster running in `data_dir`, from the first line of its
+/// `postmaster.pid` file, if the file exists.
+pub fn read_postmaster_pid(data_dir: &Path) -> Option<u32> {
+    let contents = std::fs::read_to_string(data_dir.join("postmaster.pid")).ok()?;
+    contents.lines().next()?.trim().parse().ok()
+}
+
+/// Wait until the server started as `process` has written its `postmaster.pid` file, and return
+/// the postmaster's pid. This differs from the child process's pid when running as root, where
+/// postgres is started via `sudo`. Returns `None` if the process exits or doesn't write the file
+/// within [`START_TIMEOUT`].
+pub fn wait_for_postmaster_pid(data_dir: &Path, process: &mut Child) -> Option<u32> {
+    let start = std::time::Instant::now();
+    loop {
+        if let Some(pid) = read_postmaster_pid(data_dir) {
+            return Some(pid);
+        }
+        if start.elapsed() > START_TIMEOUT || !matches!(process.try_wait(), Ok(None)) {
+            return None;
+        }
+        std::thread::sleep(CREATEDB_RETRY_DELAY);
+    }
+}
+
+/// Shut down a postgres server that is not our child process (so we can't `wait` on it) by
+/// sending it SIGINT and polling until it exits. If it hasn't exited after [`STOP_TIMEOUT`], it
+/// is sent SIGKILL, and if it still exists [`KILL_TIMEOUT`] after that, we give up on it.
+pub fn stop_detached_server(pid: u32) {
+    #[allow(clippy::cast_possible_wrap)]
+    let pid = pid as i32;
+    let _ret = unsafe { libc::kill(pid, libc::SIGINT) };
+
+    let start = std::time::Instant::now();
+    let mut killed_at = None;
+    loop {
+        // reap it if it happens to be our child after all, otherwise it stays a zombie
+        let _ret = unsafe { libc::waitpid(pid, std::ptr::null_mut(), libc::WNOHANG) };
+        #[allow(clippy::cast_sign_loss)]
+        if !process_is_running(pid as u32) {
+            break;
+        }
+        match killed_at {
+            None if start.elapsed() > STOP_TIMEOUT => {
+                let _ret = unsafe { libc::kill(pid, libc::SIGKILL) };
+                killed_at = Some(std::time::Instant::now());
+            }
+            Some(killed_at) if killed_at.elapsed() > KILL_TIMEOUT => {
+                eprintln!("pgtemp: process {} did not exit after SIGKILL", pid);
+                break;
+            }
+            _ => {}
+        }
+        std::thread::sleep(STOP_POLL_DELAY);
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs::{DirBuilder, File};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Once};
+
+use crate::run_db;
+use crate::{PgTempCluster, PgTempDBBuilder, PgTempDatabase};
+
+/// The servers started by [`shared_with`], keyed by the builder they were started with.
+static SHARED_CLUSTERS: Mutex<Vec<(PgTempDBBuilder, Arc<PgTempCluster>)>> = Mutex::new(Vec::new());
+/// The servers this process is attached to via [`shared_across_processes_with`].
+static CROSS_PROCESS_SERVERS: Mutex<Vec<(PgTempDBBuilder, Arc<CrossProcessServer>)>> =
+    Mutex::new(Vec::new());
+static REGISTER_SHUTDOWN: Once = Once::new();
+
+/// Returns a new, empty database on a PostgreSQL server that is shared by the whole process. See
+/// [`shared_with`].
+pub fn shared() -> PgTempDatabase {
+    shared_with(&PgTempDBBuilder::new())
+}
+
+/// Returns a new database on a PostgreSQL server that is shared by the whole process, e.g. by all
+/// the tests in a test binary, rather than starting a server per test.
+///
+/// The first call with a given builder configuration starts a [`PgTempCluster`] from it, and
+/// every call (including the first) creates a new database on that cluster which is dropped when
+/// the returned [`PgTempDatabase`] is dropped. Calls with a different configuration get a different
+/// server.
+///
+/// Since statics are never dropped, the shared servers are shut down by an `atexit` hook when the
+/// process exits normally, which is what the standard test harness does once all tests have run.
+pub fn shared_with(builder: &PgTempDBBuilder) -> PgTempDatabase {
+    register_shutdown_hook();
+
+    let cluster = {
+        let mut clusters = SHARED_CLUSTERS
+            .lock()
+            .expect("shared pgtemp servers lock poisoned");
+        if let Some((_, cluster)) = clusters.iter().find(|(b, _)| b == builder) {
+            cluster.clone()
+        } else {
+            let cluster = Arc::new(builder.clone().start_cluster());
+            clusters.push((builder.clone(), cluster.clone()));
+            cluster
+        }
+    };
+
+    cluster.create_database()
+}
+
+/// Returns a new, empty database on a PostgreSQL server that is shared with other processes. See
+/// [`shared_across_processes_with`].
+pub fn shared_across_processes() -> PgTempDatabase {
+    shared_across_processes_with(&PgTempDBBuilder::new())
+}
+
+/// Like [`shared_with`], but the server is shared by every process using the same builder
+/// configuration, not just the current one. This is useful with test runners like
+/// `cargo nextest` that run each test in its own process.
+///
+/// The processes coordinate through a directory under `$XDG_RUNTIME_DIR` (or the system temp
+/// directory if it is unset), which is locked with `flock` while attaching and detaching. The
+/// first process to need a server starts it and records its details there, later processes attach
+/// to it, and the last process to detach when it exits stops the server and deletes its data
+/// directory. Processes that were killed before they could detach are noticed and ignored by the
+/// next process to attach or detach, and a server that is no longer running is replaced.
+pub fn shared_across_processes_with(builder: &PgTempDBBuilder) -> PgTempDatabase {
+    register_shutdown_hook();
+
+    let server = {
+        let mut servers = CROSS_PROCESS_SERVERS
+            .lock()
+            .expect("shared pgtemp servers lock poisoned");
+        if let Some((_, server)) = servers.iter().find(|(b, _)| b == builder) {
+            server.clone()
+        } else {
+            let server = Arc::new(CrossProcessServer::attach(builder));
+            servers.push((builder.clone(), server.clone()));
+            server
+        }
+    };
+
+    server.create_database()
+}
+
+fn register_shutdown_hook() {
+    REGISTER_SHUTDOWN.call_once(|| {
+        let ret = unsafe { libc::atexit(shutdown_shared_servers) };
+        assert!(ret == 0, "failed to register pgtemp shutdown hook");
+    });
+}
+
+extern "C" fn shutdown_shared_servers() {
+    // unwinding out of an extern "C" function aborts the process, so catch any panics here
+    let res = std::panic::catch_unwind(|| {
+        let clusters = std::mem::take(
+            &mut *SHARED_CLUSTERS
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner),
+        );
+        drop(clusters);
+
+        let servers = std::mem::take(
+            &mut *CROSS_PROCESS_SERVERS
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner),
+        );
+        for (_, server) in servers {
+            server.detach();
+        }
+    });
+    if res.is_err() {
+        eprintln!("pgtemp: failed to shut down shared servers on exit");
+    }
+}
+
+/// A server shared between processes. Its details are stored in the `server` file in the
+/// coordination directory, and each attached process has a file named after its pid in the
+/// `clients` subdirectory.
+struct CrossProcessServer {
+    coordination_dir: PathBuf,
+    state: ServerState,
+    bin_path: Option<PathBuf>,
+    persist: bool,
+    next_database_id: AtomicUsize,
+}
+
+impl CrossProcessServer {
+    /// Attach to the server for this builder configuration, starting it if necessary.
+    fn attach(builder: &PgTempDBBuilder) -> CrossProcessServer {
+        let coordination_dir = coordination_dir(builder);
+        create_private_dir(&coordination_dir);
+        let clients_dir = coordination_dir.join("clients");
+        create_private_dir(&clients_dir);
+
+        let _lock = CoordinationLock::acquire(&coordination_dir);
+        remove_dead_clients(&clients_dir);
+
+        let state_path = coordination_dir.join("server");
+        let mut state = ServerState::read(&state_path);
+        if let Some(stale) = state
+            .as_ref()
+            .filter(|s| !run_db::process_is_running(s.pid))
+        {
+            // the server died or was killed, so start a new one
+            let _res = std::fs::remove_dir_all(&stale.temp_dir);
+            let _res = std::fs::remove_file(&state_path);
+            state = None;
+        }
+
+        let state = state.unwrap_or_else(|| {
+            let db = builder.clone().start();
+            let dbuser = db.db_user().to_string();
+            let dbpass = db.db_pass().to_string();
+            let dbport = db.db_port();
+            let dbname = db.db_name().to_string();
+            let ssl_root_cert = db.tls_ca_cert();
+            let (pid, temp_dir) = db.detach();
+
+            let state = ServerState {
+                pid,
+                temp_dir,
+                dbuser,
+                dbpass,
+                dbport,
+                dbname,
+                ssl_root_cert,
+            };
+            state.write(&state_path);
+            state
+        });
+
+        std::fs::write(clients_dir.join(std::process::id().to_string()), "")
+            .expect("failed to register with shared pgtemp server");
+
+        CrossProcessServer {
+            coordination_dir,
+            state,
+            bin_path: builder.bin_path.clone(),
+            persist: builder.persist_data_dir,
+            next_database_id: AtomicUsize::new(0),
+        }
+    }
+
+    fn create_database(&self) -> PgTempDatabase {
+        let id = self.next_database_id.fetch_add(1, Ordering::Relaxed);
+        // the pid keeps the names unique across processes
+        let name = format!("pgtempdb_{}_{}", std::process::id(), id);
+        PgTempDatabase::create(
+            &self.state.dbuser,
+            &self.state.dbpass,
+            self.state.dbport,
+            &name,
+            &self.state.dbname,
+            self.state.ssl_root_cert.clone(),
+            self.bin_path.clone(),
+        )
+    }
+
+    /// Detach from the server, stopping it if no other processes are attached.
+    fn detach(&self) {
+        let clients_dir = self.coordination_dir.join("clients");
+        let _lock = CoordinationLock::acquire(&self.coordination_dir);
+
+        let _res = std::fs::remove_file(clients_dir.join(std::process::id().to_string()));
+        remove_dead_clients(&clients_dir);
+
+        let attached = std::fs::read_dir(&clients_dir)
+            .map(|entries| entries.count())
+            .unwrap_or(0);
+        if attached > 0 {
+            return;
+        }
+
+        run_db::stop_detached_server(self.state.pid);
+        if !self.persist {
+            let _res = std::fs::remove_dir_all(&self.state.temp_dir);
+        }
+        let _res = std::fs::remove_file(self.coordination_dir.join("server"));
+    }
+}
+
+/// The details of a running shared server
+struct ServerState {
+    pid: u32,
+    temp_dir: PathBuf,
+    dbuser: String,
+    dbpass: String,
+    dbport: u16,
+    dbname: String,
+    /// the server's CA certificate, if it has TLS enabled
+    ssl_root_cert: Option<PathBuf>,
+}
+
+impl ServerState {
+    fn read(path: &Path) -> Option<ServerState> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        let fields: BTreeMap<&str, &str> = contents
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .collect();
+
+        Some(ServerState {
+            pid: fields.get("pid")?.parse().ok()?,
+            temp_dir: PathBuf::from(fields.get("temp_dir")?),
+            dbuser: (*fields.get("user")?).to_string(),
+            dbpass: (*fields.get("password")?).to_string(),
+            dbport: fields.get("port")?.parse().ok()?,
+            dbname: (*fields.get("dbname")?).to_string(),
+            ssl_root_cert: fields.get("ssl_root_cert").map(PathBuf::from),
+        })
+    }
+
+    fn write(&self, path: &Path) {
+        let mut contents = format!(
+            "pid={}\ntemp_dir={}\nuser={}\npassword={}\nport={}\ndbname={}\n",
+            self.pid,
+            self.temp_dir.display(),
+            self.dbuser,
+            self.dbpass,
+            self.dbport,
+            self.dbname
+        );
+        if let Some(ssl_root_cert) = &self.ssl_root_cert {
+            contents.push_str(&format!("ssl_root_cert={}\n", ssl_root_cert.display()));
+        }
+        // the state includes the superuser's password
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)
+            .expect("failed to write shared pgtemp server state");
+        // in case the file was created by an older version
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .expect("failed to set permissions of shared pgtemp server state");
+        file.write_all(contents.as_bytes())
+            .expect("failed to write shared pgtemp server state");
+    }
+}
+
+/// Remove the files of attached processes that no longer exist, e.g. because they were killed
+/// before they could detach.
+fn remove_dead_clients(clients_dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(clients_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let pid = entry.file_name().to_string_lossy().parse::<u32>();
+        if !pid.is_ok_and(run_db::process_is_running) {
+            let _res = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Create `dir` if it doesn't exist, accessible only by the current user. The coordination
+/// directory is at a predictable path, so panic if another user created it.
+fn create_private_dir(dir: &Path) {
+    let res = DirBuilder::new().mode(0o700).create(dir);
+    if let Err(e) = res {
+        assert!(
+            e.kind() == std::io::ErrorKind::AlreadyExists,
+            "failed to create pgtemp coordination directory {:?}: {}",
+            dir,
+            e
+        );
+    }
+
+    let metadata = std::fs::symlink_metadata(dir).expect(&format!(
+        "failed to read pgtemp coordination directory {:?}",
+        dir
+    ));
+    let uid = unsafe { libc::getuid() };
+    assert!(
+        metadata.is_dir() && metadata.uid() == uid,
+        "pgtemp coordination directory {:?} must be a directory owned by uid {}",
+        dir,
+        uid
+    );
+    // in case the directory was created by an older version
+    if metadata.permissions().mode() & 0o077 != 0 {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700)).expect(&format!(
+            "failed to set permissions of pgtemp coordination directory {:?}",
+            dir
+        ));
+    }
+}
+
+/// The directory used to coordinate the server for this builder configuration.
+fn coordination_dir(builder: &PgTempDBBuilder) -> PathBuf {
+    let runtime_dir =
+        std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, PathBuf::from);
+    let uid = unsafe { libc::getuid() };
+
+    runtime_dir.join(format!("pgtemp-shared-{}-{}", uid, config_key(builder)))
+}
+
+/// A key identifying the builder's configuration that is the same in every process
+fn config_key(builder: &PgTempDBBuilder) -> String {
+    // HashMap iteration order differs between processes, so sort the maps
+    let mut builder = builder.clone();
+    let server_configs: BTreeMap<_, _> = builder.server_configs.drain().collect();
+    let initdb_args: BTreeMap<_, _> = builder.initdb_args.drain().collect();
+
+    let mut hasher = DefaultHasher::new();
+    format!("{:?} {:?} {:?}", builder, server_configs, initdb_args).hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// An exclusive `flock` on the coordination directory's lock file, released on drop.
+struct CoordinationLock(File);
+
+impl CoordinationLock {
+    fn acquire(coordination_dir: &Path) -> CoordinationLock {
+        let file = File::create(coordination_dir.join("lock"))
+            .expect("failed to open pgtemp coordination lock file");
+        let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) };
+        assert!(
+            ret == 0,
+            "failed to lock pgtemp coordination lock file: {}",
+            std::io::Error::last_os_error()
+        );
+
+        CoordinationLock(file)
+    }
+}
+
+impl Drop for CoordinationLock {
+    fn drop(&mut self) {
+        let _ret = unsafe { libc::flock(self.0.as_raw_fd(), libc::LOCK_UN) };
+    }
+}
@@ -0,0 +1,87 @@
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// The minimum free space needed to create a data directory. A freshly initialized data directory
+/// takes around 40 MiB, and the server needs some room for WAL on top of that.
+const MIN_FREE_SPACE: u64 = 64 * 1024 * 1024;
+
+/// The `f_type` reported by `statfs` for tmpfs filesystems
+#[cfg(target_os = "linux")]
+const TMPFS_MAGIC: libc::c_long = 0x0102_1994;
+
+/// The directory to create data directories in when memory-backed storage is requested: the first
+/// of `/dev/shm` and `$XDG_RUNTIME_DIR` that is a writable tmpfs. If there is none, a warning is
+/// printed and `None` is returned so that the system temp dir is used instead.
+///
+/// Panics if the directory doesn't have enough free space for a data directory.
+pub(crate) fn memory_backed_dir() -> Option<PathBuf> {
+    let candidates = std::iter::once(PathBuf::from("/dev/shm"))
+        .chain(std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from));
+
+    for dir in candidates {
+        if is_tmpfs(&dir) && is_writable(&dir) {
+            check_free_space(&dir);
+            return Some(dir);
+        }
+    }
+
+    eprintln!(
+        "pgtemp: no memory-backed (tmpfs) directory found in /dev/shm or $XDG_RUNTIME_DIR, \
+         using the system temp dir instead"
+    );
+    None
+}
+
+/// Panic with a clear message if `dir` doesn't have room for a data directory, rather than letting
+/// initdb fail part way through.
+fn check_free_space(dir: &Path) {
+    let Some(free) = free_space(dir) else {
+        return;
+    };
+    assert!(
+        free >= MIN_FREE_SPACE,
+        "not enough free space in {:?} for a pgtemp data directory: {} MiB available, at least \
+         {} MiB needed",
+        dir,
+        free / 1024 / 1024,
+        MIN_FREE_SPACE / 1024 / 1024
+    );
+}
+
+/// The space available to unprivileged users in the filesystem containing `dir`, in bytes
+fn free_space(dir: &Path) -> Option<u64> {
+    let path = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::statvfs(path.as_ptr(), &mut stat) };
+    if ret != 0 {
+        return None;
+    }
+    #[allow(clippy::useless_conversion)] // the field types differ between platforms
+    Some(u64::from(stat.f_bavail) * u64::from(stat.f_frsize))
+}
+
+#[cfg(target_os = "linux")]
+fn is_tmpfs(dir: &Path) -> bool {
+    let Ok(path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::statfs(path.as_ptr(), &mut stat) };
+    #[allow(clippy::unnecessary_cast)] // the field's type differs between libcs
+    let f_type = stat.f_type as libc::c_long;
+    ret == 0 && f_type == TMPFS_MAGIC
+}
+
+/// tmpfs can only be detected via `statfs` on Linux
+#[cfg(not(target_os = "linux"))]
+fn is_tmpfs(_dir: &Path) -> bool {
+    false
+}
+
+fn is_writable(dir: &Path) -> bool {
+    let Ok(path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) == 0 }
+}
@@ -0,0 +1,77 @@
+use std::io::Write;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+/// The supervisor waits for a line on stdin. If it gets `release`, the owning process has taken
+/// care of the server (or handed it off), so it exits. If stdin is closed without it, the owning
+/// process died, so it stops the server the same way `run_db::stop_detached_server` does and
+/// removes the temp dir. It runs in its own process group and ignores SIGINT, SIGHUP and SIGTERM,
+/// so that e.g. pressing ctrl-C in the terminal running the tests or a CI timeout stopping the
+/// whole process group doesn't stop the supervisor before it can clean up.
+const SUPERVISOR_SCRIPT: &str = r#"
+trap '' INT HUP TERM
+pid="$1"
+temp_dir="$2"
+persist="$3"
+if read -r line && [ "$line" = release ]; then
+    exit 0
+fi
+kill -INT "$pid" 2>/dev/null
+tries=0
+while kill -0 "$pid" 2>/dev/null; do
+    tries=$((tries + 1))
+    if [ "$tries" -eq 300 ]; then
+        kill -KILL "$pid" 2>/dev/null
+    elif [ "$tries" -gt 350 ]; then
+        break
+    fi
+    sleep 0.1
+done
+if [ "$persist" != 1 ]; then
+    rm -rf "$temp_dir"
+fi
+"#;
+
+/// A small `sh` process that stops the postgres server and deletes its temp dir if the process
+/// that started it dies without shutting it down, e.g. because it was killed by a CI timeout.
+///
+/// It notices via the pipe connected to its stdin: the write end is only held by this process, so
+/// the kernel closes it when this process exits, however that happens.
+pub(crate) struct Supervisor {
+    process: Child,
+    stdin: Option<ChildStdin>,
+}
+
+impl Supervisor {
+    /// Start supervising the server. `postmaster_pid` must be the pid of the postgres server
+    /// itself rather than e.g. `sudo` running it, since that is what has to be stopped.
+    pub(crate) fn spawn(postmaster_pid: u32, temp_dir: &Path, persist: bool) -> Supervisor {
+        let mut process = Command::new("sh")
+            .arg("-c")
+            .arg(SUPERVISOR_SCRIPT)
+            .arg("pgtemp-supervisor")
+            .arg(postmaster_pid.to_string())
+            .arg(temp_dir)
+            .arg(if persist { "1" } else { "0" })
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()
+            .expect("failed to start pgtemp supervisor process");
+        let stdin = process.stdin.take();
+
+        Supervisor { process, stdin }
+    }
+
+    /// Tell the supervisor that the server no longer needs supervising, because it is being shut
+    /// down normally or handed off, and wait for it to exit.
+    pub(crate) fn release(mut self) {
+        if let Some(mut stdin) = self.stdin.take() {
+            // if this fails the supervisor has already exited
+            let _res = stdin.write_all(b"release\n");
+        }
+        let _status = self.process.wait();
+    }
+}
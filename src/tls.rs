@@ -0,0 +1,146 @@
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DnType, IsCa, KeyPair, KeyUsagePurpose,
+};
+
+use crate::run_db;
+
+/// A client certificate issued by [`PgTempDB::issue_client_cert`](crate::PgTempDB::issue_client_cert),
+/// for use with the `sslcert` and `sslkey` connection parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCert {
+    /// The path to the PEM-encoded certificate
+    pub cert_path: PathBuf,
+    /// The path to the PEM-encoded private key, which is only readable by the current user
+    pub key_path: PathBuf,
+}
+
+/// The throwaway CA and server certificate of a server started with
+/// [`PgTempDBBuilder::with_tls`](crate::PgTempDBBuilder::with_tls). The CA's key is kept in memory
+/// so that client certificates can be issued later.
+pub(crate) struct TlsCerts {
+    dir: PathBuf,
+    ca_cert: Certificate,
+    ca_key: KeyPair,
+    next_client_id: AtomicUsize,
+}
+
+impl TlsCerts {
+    /// Generate a CA and a server certificate for `localhost`, `127.0.0.1` and `::1` signed by
+    /// it, and write them to the `tls` directory in `temp_dir`.
+    pub(crate) fn generate(temp_dir: &Path) -> TlsCerts {
+        let dir = temp_dir.join("tls");
+        std::fs::create_dir(&dir).expect("failed to create tls directory");
+
+        let ca_key = KeyPair::generate().expect("failed to generate CA key");
+        let mut ca_params =
+            CertificateParams::new(Vec::new()).expect("failed to create CA certificate params");
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params
+            .distinguished_name
+            .push(DnType::CommonName, "pgtemp CA");
+        ca_params.key_usages = vec![
+            KeyUsagePurpose::KeyCertSign,
+            KeyUsagePurpose::CrlSign,
+            KeyUsagePurpose::DigitalSignature,
+        ];
+        let ca_cert = ca_params
+            .self_signed(&ca_key)
+            .expect("failed to generate CA certificate");
+
+        let certs = TlsCerts {
+            dir,
+            ca_cert,
+            ca_key,
+            next_client_id: AtomicUsize::new(0),
+        };
+        write_file(&certs.ca_cert_path(), &certs.ca_cert.pem(), 0o644);
+
+        let (server_cert, server_key) = certs.issue(
+            &["localhost".into(), "127.0.0.1".into(), "::1".into()],
+            "localhost",
+        );
+        write_file(&certs.server_cert_path(), &server_cert, 0o644);
+        // postgres refuses to use a key that is readable by other users or that it doesn't own
+        write_file(&certs.server_key_path(), &server_key, 0o600);
+        if run_db::current_user_is_root() {
+            run_db::chown_to_postgres(&certs.server_key_path());
+        }
+
+        certs
+    }
+
+    /// The server configuration parameters that enable TLS with these certificates. The CA is
+    /// also used to verify client certificates.
+    pub(crate) fn server_configs(&self) -> Vec<(String, String)> {
+        vec![
+            ("ssl".into(), "on".into()),
+            ("ssl_cert_file".into(), path_str(&self.server_cert_path())),
+            ("ssl_key_file".into(), path_str(&self.server_key_path())),
+            ("ssl_ca_file".into(), path_str(&self.ca_cert_path())),
+        ]
+    }
+
+    pub(crate) fn ca_cert_path(&self) -> PathBuf {
+        self.dir.join("ca.crt")
+    }
+
+    fn server_cert_path(&self) -> PathBuf {
+        self.dir.join("server.crt")
+    }
+
+    fn server_key_path(&self) -> PathBuf {
+        self.dir.join("server.key")
+    }
+
+    /// Issue a client certificate for `user`, i.e. with `user` as its common name.
+    pub(crate) fn issue_client_cert(&self, user: &str) -> ClientCert {
+        let id = self.next_client_id.fetch_add(1, Ordering::Relaxed);
+        let cert_path = self.dir.join(format!("client-{}.crt", id));
+        let key_path = self.dir.join(format!("client-{}.key", id));
+
+        let (cert, key) = self.issue(&[], user);
+        write_file(&cert_path, &cert, 0o644);
+        // libpq refuses to use a key that is readable by other users
+        write_file(&key_path, &key, 0o600);
+
+        ClientCert {
+            cert_path,
+            key_path,
+        }
+    }
+
+    /// Issue a certificate signed by the CA, returning the PEM-encoded certificate and key.
+    fn issue(&self, subject_alt_names: &[String], common_name: &str) -> (String, String) {
+        let key = KeyPair::generate().expect("failed to generate key");
+        let mut params =
+            CertificateParams::new(subject_alt_names).expect("failed to create certificate params");
+        params
+            .distinguished_name
+            .push(DnType::CommonName, common_name);
+        let cert = params
+            .signed_by(&key, &self.ca_cert, &self.ca_key)
+            .expect("failed to generate certificate");
+
+        (cert.pem(), key.serialize_pem())
+    }
+}
+
+fn write_file(path: &Path, contents: &str, mode: u32) {
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(path)
+        .expect(&format!("failed to create {:?}", path));
+    file.write_all(contents.as_bytes())
+        .expect(&format!("failed to write {:?}", path));
+}
+
+fn path_str(path: &Path) -> String {
+    path.to_str().unwrap().to_string()
+}
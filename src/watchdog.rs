@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::output::{ServerOutput, OUTPUT_TAIL_LINES};
+
+/// What the watchdog enabled by
+/// [`PgTempDBBuilder::with_watchdog`](crate::PgTempDBBuilder::with_watchdog) does when the
+/// postgres server exits unexpectedly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum WatchdogAction {
+    /// Print a report with the tail of the server's output to stderr as soon as the server exits
+    Log,
+    /// Panic with the report the next time the [`PgTempDB`](crate::PgTempDB)'s connection details
+    /// are used, e.g. via `connection_uri`, so that the test using it fails with the reason
+    Panic,
+}
+
+/// Watches a postgres server process from a background thread and reports if it exits without
+/// having been shut down by us.
+pub(crate) struct Watchdog {
+    action: WatchdogAction,
+    state: Arc<WatchdogState>,
+}
+
+#[derive(Default)]
+struct WatchdogState {
+    /// set before we shut down or detach the server, so that its exit is not reported
+    stopping: AtomicBool,
+    /// the crash report, once the server has exited unexpectedly
+    report: Mutex<Option<String>>,
+}
+
+impl Watchdog {
+    /// Start watching `process`. The tail of the server's output is included in the report, from
+    /// `log_path` if it is written to a log file and from `output` otherwise.
+    pub(crate) fn start(
+        process: &Child,
+        log_path: Option<PathBuf>,
+        output: Option<ServerOutput>,
+        action: WatchdogAction,
+    ) -> Watchdog {
+        let state = Arc::new(WatchdogState::default());
+
+        #[allow(clippy::cast_possible_wrap)]
+        let pid = process.id() as libc::pid_t;
+        let thread_state = Arc::clone(&state);
+        let _handle = std::thread::spawn(move || {
+            let status = wait_without_reaping(pid);
+            if thread_state.stopping.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let output = match (&log_path, &output) {
+                (Some(log_path), _) => log_file_tail(log_path),
+                (None, Some(output)) => output.tail_after_exit(),
+                (None, None) => String::new(),
+            };
+            let report = format!(
+                "postgres server (pid {}) exited unexpectedly ({})\n\nlast lines of server output:\n{}",
+                pid,
+                status,
+                if output.is_empty() { "(none)" } else { &output }
+            );
+
+            if action == WatchdogAction::Log {
+                eprintln!("pgtemp: {}", report);
+            }
+            *thread_state.report.lock().unwrap() = Some(report);
+        });
+
+        Watchdog { action, state }
+    }
+
+    /// Stop reporting, before the server is shut down or detached
+    pub(crate) fn stop(&self) {
+        self.state.stopping.store(true, Ordering::SeqCst);
+    }
+
+    /// Panic with the crash report if the server has exited unexpectedly and the action is
+    /// [`WatchdogAction::Panic`].
+    pub(crate) fn check(&self) {
+        if self.action != WatchdogAction::Panic {
+            return;
+        }
+        if let Some(report) = self.state.report.lock().unwrap().as_ref() {
+            panic!("{}", report);
+        }
+    }
+}
+
+/// Block until the process exits, without reaping it so that the `Child` can still be waited on.
+/// Returns a description of how it exited.
+fn wait_without_reaping(pid: libc::pid_t) -> String {
+    #[allow(clippy::cast_sign_loss)]
+    let id = pid as libc::id_t;
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    loop {
+        let ret =
+            unsafe { libc::waitid(libc::P_PID, id, &mut info, libc::WEXITED | libc::WNOWAIT) };
+        if ret == 0 {
+            return describe_exit(&info);
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::Interrupted {
+            // e.g. ECHILD if the process was already reaped via `is_running` or `wait_for_exit`
+            return "exit status unknown".into();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn describe_exit(info: &libc::siginfo_t) -> String {
+    let status = unsafe { info.si_status() };
+    match info.si_code {
+        libc::CLD_EXITED => format!("exit code {}", status),
+        libc::CLD_KILLED | libc::CLD_DUMPED => format!("killed by signal {}", status),
+        _ => "exit status unknown".into(),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn describe_exit(info: &libc::siginfo_t) -> String {
+    match info.si_code {
+        libc::CLD_EXITED => format!("exit code {}", info.si_status),
+        libc::CLD_KILLED | libc::CLD_DUMPED => format!("killed by signal {}", info.si_status),
+        _ => "exit status unknown".into(),
+    }
+}
+
+/// The last lines of the log file
+fn log_file_tail(log_path: &Path) -> String {
+    let Ok(contents) = std::fs::read_to_string(log_path) else {
+        return format!("(failed to read log file {:?})", log_path);
+    };
+    let lines: Vec<&str> = contents.lines().collect();
+    lines[lines.len().saturating_sub(OUTPUT_TAIL_LINES)..].join("\n")
+}
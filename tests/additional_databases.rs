@@ -0,0 +1,82 @@
+//! Tests for creating several databases on one server at startup
+
+use pgtemp::{AdditionalDatabase, PgTempDBBuilder};
+use sqlx::postgres::PgConnection;
+use sqlx::prelude::*;
+
+#[tokio::test]
+/// additional databases are created with their owner, load script and migrations
+async fn additional_databases() {
+    let temp = tempfile::tempdir().unwrap();
+    let migrations = temp.path().join("migrations");
+    std::fs::create_dir(&migrations).unwrap();
+    std::fs::write(
+        migrations.join("0001_orders.sql"),
+        "CREATE TABLE orders (id SERIAL PRIMARY KEY);",
+    )
+    .unwrap();
+    std::fs::write(
+        migrations.join("0002_orders_total.sql"),
+        "ALTER TABLE orders ADD COLUMN total INT NOT NULL DEFAULT 0;",
+    )
+    .unwrap();
+
+    let db = PgTempDBBuilder::new()
+        .with_dbname("main")
+        .with_role("billing", "billingpw", &[])
+        .with_additional_database("analytics", None)
+        .with_database(
+            AdditionalDatabase::new("billing")
+                .with_owner("billing")
+                .load_database("tests/fixtures/person.sql")
+                .with_migrations(&migrations),
+        )
+        .start_async()
+        .await;
+
+    let mut conn = PgConnection::connect(&db.connection_uri_for_database("billing"))
+        .await
+        .expect("failed to connect to billing db");
+    let row = sqlx::query(
+        "SELECT current_database(), pg_get_userbyid(datdba) FROM pg_database \
+         WHERE datname = current_database()",
+    )
+    .fetch_one(&mut conn)
+    .await
+    .expect("failed to execute query");
+    let dbname: &str = row.get(0);
+    let owner: &str = row.get(1);
+    assert_eq!(dbname, "billing");
+    assert_eq!(owner, "billing");
+
+    // the load script and migrations ran in the billing database only
+    sqlx::query("SELECT * FROM person")
+        .fetch_all(&mut conn)
+        .await
+        .expect("load script did not run");
+    sqlx::query("SELECT total FROM orders")
+        .fetch_all(&mut conn)
+        .await
+        .expect("migrations did not run");
+
+    let mut main_conn = PgConnection::connect(&db.connection_uri())
+        .await
+        .expect("failed to connect to main db");
+    assert!(sqlx::query("SELECT * FROM orders")
+        .fetch_all(&mut main_conn)
+        .await
+        .is_err());
+
+    PgConnection::connect(&db.connection_uri_for_database("analytics"))
+        .await
+        .expect("failed to connect to analytics db");
+}
+
+#[test]
+#[should_panic(expected = "psql failed")]
+/// an unknown owner fails startup
+fn additional_database_unknown_owner() {
+    let _db = PgTempDBBuilder::new()
+        .with_additional_database("other", Some("nobody"))
+        .start();
+}
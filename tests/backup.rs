@@ -0,0 +1,96 @@
+//! Tests for WAL archiving and point-in-time recovery
+
+use pgtemp::{PgTempDB, PgTempDBBuilder, RecoveryTarget};
+
+fn ids(db: &PgTempDB) -> Vec<Vec<Option<String>>> {
+    db.query("SELECT id FROM items ORDER BY id")
+}
+
+fn some(values: &[&str]) -> Vec<Vec<Option<String>>> {
+    values.iter().map(|v| vec![Some(v.to_string())]).collect()
+}
+
+#[test]
+/// a backup can be restored to a restore point, a time or an LSN after it was taken, or to the
+/// end of the archived WAL
+fn restore_to_targets() {
+    let db = PgTempDBBuilder::new()
+        .with_dbname("backed_up")
+        .with_wal_archiving()
+        .start();
+    db.execute("CREATE TABLE items (id int)");
+    let backup = db.base_backup();
+    assert!(backup.data_dir().join("backup_label").exists());
+    // only in the WAL after the backup
+    db.execute("INSERT INTO items VALUES (1)");
+
+    let restore_point_lsn = db.create_restore_point("after_one");
+    db.execute("INSERT INTO items VALUES (2)");
+    let time = db.query("SELECT now()")[0][0].clone().unwrap();
+    db.execute("INSERT INTO items VALUES (3)");
+    let lsn = db.query("SELECT pg_current_wal_insert_lsn()")[0][0]
+        .clone()
+        .unwrap();
+    db.execute("INSERT INTO items VALUES (4)");
+    db.archive_wal();
+    assert!(db
+        .wal_archive_dir()
+        .unwrap()
+        .read_dir()
+        .unwrap()
+        .next()
+        .is_some());
+    assert!(!restore_point_lsn.is_empty());
+
+    let restore = |target| {
+        PgTempDBBuilder::new()
+            .with_dbname("backed_up")
+            .restore_from_backup(&backup, target)
+    };
+    let restored = restore(RecoveryTarget::RestorePoint("after_one".into()));
+    assert_eq!(ids(&restored), some(&["1"]));
+    // the restored server is promoted, so it accepts writes
+    restored.execute("INSERT INTO items VALUES (10)");
+
+    assert_eq!(ids(&restore(RecoveryTarget::Time(time))), some(&["1", "2"]));
+    assert_eq!(
+        ids(&restore(RecoveryTarget::Lsn(lsn))),
+        some(&["1", "2", "3"])
+    );
+    assert_eq!(
+        ids(&restore(RecoveryTarget::Latest)),
+        some(&["1", "2", "3", "4"])
+    );
+}
+
+#[test]
+/// restoring to a point that was never archived fails with the server's output
+fn restore_to_missing_target() {
+    let db = PgTempDBBuilder::new().with_wal_archiving().start();
+    let backup = db.base_backup();
+    db.archive_wal();
+
+    let res = std::thread::scope(|s| {
+        s.spawn(|| {
+            PgTempDBBuilder::new()
+                .restore_from_backup(&backup, RecoveryTarget::RestorePoint("missing".into()))
+        })
+        .join()
+    });
+    let message = *res.unwrap_err().downcast::<String>().unwrap();
+    assert!(
+        message.contains("the restored server exited during recovery")
+            && message.contains("recovery target"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+#[should_panic(expected = "base backups require WAL archiving")]
+/// backups need WAL archiving to be enabled
+fn backup_requires_archiving() {
+    let db = PgTempDB::new();
+    assert_eq!(db.wal_archive_dir(), None);
+    let _backup = db.base_backup();
+}
@@ -0,0 +1,93 @@
+//! Tests for capturing row changes with logical decoding
+
+use std::collections::BTreeMap;
+
+use pgtemp::{ChangeKind, PgTempDBBuilder};
+
+fn row(columns: &[(&str, Option<&str>)]) -> BTreeMap<String, Option<String>> {
+    columns
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.map(String::from)))
+        .collect()
+}
+
+#[test]
+/// inserts, updates and deletes are captured with their values, and each change is only returned
+/// once
+fn capture_row_changes() {
+    let db = PgTempDBBuilder::new().with_change_capture().start();
+    db.execute(
+        "CREATE TABLE items (id int PRIMARY KEY, name text, price numeric);
+         CREATE TABLE other (id int PRIMARY KEY)",
+    );
+    let capture = db.start_change_capture();
+    assert!(capture.changes().is_empty());
+
+    db.execute("INSERT INTO items VALUES (1, 'it''s a \"test\"', 1.5), (2, NULL, 2)");
+    db.execute("UPDATE items SET name = 'two words' WHERE id = 2");
+    db.execute("DELETE FROM items WHERE id = 1");
+
+    let changes = capture.changes();
+    let kinds: Vec<ChangeKind> = changes.iter().map(|c| c.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            ChangeKind::Insert,
+            ChangeKind::Insert,
+            ChangeKind::Update,
+            ChangeKind::Delete
+        ]
+    );
+    assert!(changes
+        .iter()
+        .all(|c| c.schema == "public" && c.table == "items"));
+
+    assert_eq!(
+        changes[0].columns,
+        row(&[
+            ("id", Some("1")),
+            ("name", Some("it's a \"test\"")),
+            ("price", Some("1.5"))
+        ])
+    );
+    assert_eq!(changes[1].get("name"), None);
+    assert_eq!(changes[2].get("name"), Some("two words"));
+    assert_eq!(changes[2].old_key, None);
+    assert_eq!(changes[3].columns, row(&[("id", Some("1"))]));
+
+    assert!(capture.changes().is_empty());
+    db.execute("TRUNCATE items, other");
+    assert!(capture.changes().is_empty());
+}
+
+#[test]
+/// updates that change the key include the old key, and quoted identifiers are unquoted
+fn capture_key_change() {
+    let db = PgTempDBBuilder::new().with_change_capture().start();
+    db.execute(r#"CREATE TABLE "My Items" ("Item Id" int PRIMARY KEY, tags text[])"#);
+    db.execute(r#"INSERT INTO "My Items" VALUES (1, '{a,b}')"#);
+    let capture = db.start_change_capture();
+
+    db.execute(r#"UPDATE "My Items" SET "Item Id" = 2"#);
+    let changes = capture.changes();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].table, "My Items");
+    assert_eq!(changes[0].old_key, Some(row(&[("Item Id", Some("1"))])));
+    assert_eq!(
+        changes[0].columns,
+        row(&[("Item Id", Some("2")), ("tags", Some("{a,b}"))])
+    );
+}
+
+#[test]
+/// the builder switch configures the logical WAL level that change capture needs
+fn change_capture_sets_wal_level() {
+    let db = PgTempDBBuilder::new().with_change_capture().start();
+    assert_eq!(
+        db.query("SELECT current_setting('wal_level')"),
+        [[Some("logical".to_string())]]
+    );
+    let capture = db.start_change_capture();
+    db.execute("CREATE TABLE items (id int PRIMARY KEY); INSERT INTO items VALUES (1)");
+    assert_eq!(capture.changes().len(), 1);
+}
@@ -0,0 +1,112 @@
+//! Tests for PgTempCluster
+
+use std::io::Write;
+
+use pgtemp::{PgTempCluster, PgTempDBBuilder};
+use sqlx::postgres::PgConnection;
+use sqlx::prelude::*;
+
+#[tokio::test]
+/// databases from the same cluster are on the same server but independent of each other, and
+/// contain the data loaded into the cluster on startup
+async fn cluster_databases_are_independent() {
+    let temp = tempfile::tempdir().unwrap();
+    let load_path = temp.path().join("load.sql");
+    let mut f = std::fs::File::create(&load_path).unwrap();
+    f.write_all(b"CREATE TABLE person (id SERIAL PRIMARY KEY, name TEXT NOT NULL);")
+        .unwrap();
+    drop(f);
+
+    let cluster = PgTempDBBuilder::new()
+        .load_database(&load_path)
+        .start_cluster_async()
+        .await;
+
+    let db1 = cluster.create_database();
+    let db2 = cluster.create_database();
+    assert_ne!(db1.db_name(), db2.db_name());
+    assert_eq!(db1.db_port(), db2.db_port());
+    assert_eq!(db1.db_port(), cluster.server().db_port());
+    assert_eq!(cluster.databases_in_use(), 2);
+
+    let mut conn1 = PgConnection::connect(&db1.connection_uri())
+        .await
+        .expect("failed to connect to db1");
+    let mut conn2 = PgConnection::connect(&db2.connection_uri())
+        .await
+        .expect("failed to connect to db2");
+
+    sqlx::query("INSERT INTO person (name) VALUES ('test1')")
+        .execute(&mut conn1)
+        .await
+        .expect("failed to insert name into values");
+
+    let count1: i64 = sqlx::query("SELECT count(*) FROM person")
+        .fetch_one(&mut conn1)
+        .await
+        .expect("failed to count rows")
+        .get(0);
+    let count2: i64 = sqlx::query("SELECT count(*) FROM person")
+        .fetch_one(&mut conn2)
+        .await
+        .expect("failed to count rows")
+        .get(0);
+    assert_eq!(count1, 1);
+    assert_eq!(count2, 0);
+
+    drop(conn1);
+    drop(db1);
+    assert_eq!(cluster.databases_in_use(), 1);
+
+    let rows = sqlx::query("SELECT datname FROM pg_database WHERE datname LIKE 'pgtempdb%'")
+        .fetch_all(&mut conn2)
+        .await
+        .expect("failed to list databases");
+    assert_eq!(rows.len(), 1);
+}
+
+#[test]
+/// the cluster never has more than the configured number of databases at once
+fn cluster_max_databases() {
+    let cluster = PgTempDBBuilder::new().with_max_databases(2).start_cluster();
+
+    let db1 = cluster.create_database();
+    let _db2 = cluster.create_database();
+    assert!(cluster.try_create_database().is_none());
+
+    std::thread::scope(|s| {
+        // blocks until db1 is dropped
+        let handle = s.spawn(|| cluster.create_database());
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        assert!(!handle.is_finished());
+
+        drop(db1);
+        let db3 = handle.join().expect("creating database panicked");
+        assert_eq!(cluster.databases_in_use(), 2);
+        drop(db3);
+    });
+
+    assert_eq!(cluster.databases_in_use(), 1);
+    assert!(cluster.try_create_database().is_some());
+}
+
+#[tokio::test]
+/// default cluster works
+async fn cluster_default() {
+    let cluster = PgTempCluster::new();
+    let db = cluster.create_database();
+    assert_eq!(db.db_name(), "pgtempdb_0");
+    assert_eq!(db.db_user(), "postgres");
+    assert_eq!(db.db_port(), cluster.server().db_port());
+    assert_eq!(cluster.databases_in_use(), 1);
+
+    let mut conn = PgConnection::connect(&db.connection_uri())
+        .await
+        .expect("failed to connect to db");
+    let name: String = sqlx::query("SELECT current_database()::text")
+        .fetch_one(&mut conn)
+        .await
+        .expect("failed to select database name")
+        .get(0);
+    assert_eq!(name, "pgtempdb_0");
+}
@@ -0,0 +1,78 @@
+//! Tests for loading the builder from a pgtemp.toml file
+#![cfg(feature = "serde")]
+
+use pgtemp::PgTempDBBuilder;
+use sqlx::postgres::PgConnection;
+use sqlx::prelude::*;
+
+#[tokio::test]
+/// settings are read from the file, and relative paths are relative to the file's directory
+async fn builder_from_config_file() {
+    let temp = tempfile::tempdir().unwrap();
+    std::fs::create_dir(temp.path().join("fixtures")).unwrap();
+    std::fs::write(
+        temp.path().join("fixtures/schema.sql"),
+        "CREATE TABLE person (id SERIAL PRIMARY KEY, name TEXT NOT NULL);",
+    )
+    .unwrap();
+
+    let config_path = temp.path().join("pgtemp.toml");
+    std::fs::write(
+        &config_path,
+        r#"
+dbname = "configdb"
+load_path = "fixtures/schema.sql"
+extensions = ["pgcrypto"]
+
+[server_configs]
+max_connections = 42
+geqo = false
+application_name = "from_config"
+"#,
+    )
+    .unwrap();
+
+    let builder = PgTempDBBuilder::from_config_file(&config_path);
+    assert_eq!(builder.get_dbname(), "configdb");
+    assert_eq!(
+        builder.load_path.as_deref(),
+        Some(temp.path().join("fixtures/schema.sql").as_path())
+    );
+    assert_eq!(builder.server_configs["max_connections"], "42");
+    assert_eq!(builder.server_configs["geqo"], "off");
+
+    let db = builder.start_async().await;
+    let mut conn = PgConnection::connect(&db.connection_uri())
+        .await
+        .expect("failed to connect to db");
+
+    let row = sqlx::query(
+        "SELECT current_database(), current_setting('max_connections'), current_setting('geqo')",
+    )
+    .fetch_one(&mut conn)
+    .await
+    .expect("failed to execute query");
+    let dbname: &str = row.get(0);
+    let max_connections: &str = row.get(1);
+    let geqo: &str = row.get(2);
+    assert_eq!(dbname, "configdb");
+    assert_eq!(max_connections, "42");
+    assert_eq!(geqo, "off");
+
+    // the schema was loaded and the extension was created
+    sqlx::query("INSERT INTO person (name) VALUES (encode(gen_random_bytes(4), 'hex'))")
+        .execute(&mut conn)
+        .await
+        .expect("failed to insert");
+}
+
+#[test]
+#[should_panic(expected = "invalid pgtemp config file")]
+/// unknown keys are rejected
+fn config_file_unknown_key() {
+    let temp = tempfile::tempdir().unwrap();
+    let config_path = temp.path().join("pgtemp.toml");
+    std::fs::write(&config_path, "db_name = \"typo\"\n").unwrap();
+
+    PgTempDBBuilder::from_config_file(&config_path);
+}
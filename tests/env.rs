@@ -0,0 +1,81 @@
+//! Tests for configuring the builder from environment variables. Environment variables are
+//! process-wide, so there is only one test in this file.
+
+use pgtemp::PgTempDBBuilder;
+use sqlx::postgres::PgConnection;
+use sqlx::prelude::*;
+
+#[tokio::test]
+/// environment variables are applied by `from_env` but not `new`, and explicit calls override them
+async fn builder_from_env() {
+    let temp = tempfile::tempdir().unwrap();
+    let log_path = temp.path().join("postgres.log");
+
+    std::env::set_var("PGTEMP_USER", "envuser");
+    std::env::set_var("PGTEMP_DBNAME", "envdb");
+    std::env::set_var("PGTEMP_PERSIST", "1");
+    std::env::set_var("PGTEMP_MEMORY_BACKED_STORAGE", "yes");
+    std::env::set_var("PGTEMP_SERVER_PROFILE", "production-like");
+    std::env::set_var("PGTEMP_DATA_DIR_PREFIX", temp.path());
+    std::env::set_var("PGTEMP_LOG", &log_path);
+    std::env::set_var("PGTEMP_CONFIG_MAX_CONNECTIONS", "55");
+    std::env::set_var("PGTEMP_CONFIG_geqo", "off");
+    std::env::set_var("PGTEMP_INITDB_WAL_SEGSIZE", "32");
+
+    let builder = PgTempDBBuilder::from_env();
+    assert_eq!(builder.get_user(), "envuser");
+    assert_eq!(builder.get_dbname(), "envdb");
+    assert!(builder.persist_data_dir);
+    assert!(builder.memory_backed_storage);
+    assert_eq!(
+        builder.server_profile,
+        pgtemp::ServerProfile::ProductionLike
+    );
+    assert_eq!(builder.temp_dir_prefix.as_deref(), Some(temp.path()));
+    assert_eq!(builder.log_path.as_deref(), Some(log_path.as_path()));
+    assert_eq!(builder.server_configs["max_connections"], "55");
+    assert_eq!(builder.server_configs["geqo"], "off");
+    assert_eq!(builder.initdb_args["wal-segsize"], "32");
+
+    // new and default ignore the environment
+    assert_eq!(PgTempDBBuilder::new(), PgTempDBBuilder::default());
+    assert_eq!(PgTempDBBuilder::new().get_user(), "postgres");
+
+    // explicit calls override the environment
+    let builder = PgTempDBBuilder::from_env()
+        .with_dbname("explicitdb")
+        .with_config_param("geqo", "on");
+    assert_eq!(builder.get_user(), "envuser");
+    assert_eq!(builder.get_dbname(), "explicitdb");
+    assert_eq!(builder.server_configs["geqo"], "on");
+
+    let db = builder.persist_data(false).start_async().await;
+    let mut conn = PgConnection::connect(&db.connection_uri())
+        .await
+        .expect("failed to connect to db");
+
+    let row = sqlx::query(
+        "SELECT current_user, current_setting('max_connections'), current_setting('wal_segment_size')",
+    )
+        .fetch_one(&mut conn)
+        .await
+        .expect("failed to execute query");
+    let user: &str = row.get(0);
+    let max_connections: &str = row.get(1);
+    let wal_segment_size: &str = row.get(2);
+    assert_eq!(user, "envuser");
+    assert_eq!(max_connections, "55");
+    assert_eq!(wal_segment_size, "32MB");
+
+    drop(conn);
+    db.async_shutdown().await;
+
+    let log = std::fs::read_to_string(&log_path).expect("failed to read postgres log");
+    assert!(log.contains("database system is ready to accept connections"));
+
+    for (key, _) in std::env::vars() {
+        if key.starts_with("PGTEMP_") {
+            std::env::remove_var(key);
+        }
+    }
+}
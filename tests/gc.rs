@@ -0,0 +1,236 @@
+//! Tests for cleaning up after processes that were killed without shutting down their servers,
+//! via `gc` or the supervisor process
+
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+
+use pgtemp::PgTempDBBuilder;
+
+#[test]
+#[ignore = "run by the other tests in this file in separate processes"]
+/// starts a server in the given prefix, prints its details, and then waits for stdin to be closed
+fn gc_child() {
+    let prefix = std::env::var("PGTEMP_TEST_PREFIX").unwrap();
+    let persist = std::env::var("PGTEMP_TEST_PERSIST").is_ok();
+    let supervise = std::env::var("PGTEMP_TEST_SUPERVISE").is_ok();
+    let db = PgTempDBBuilder::new()
+        .with_data_dir_prefix(prefix)
+        .persist_data(persist)
+        .kill_on_owner_exit(supervise)
+        .start();
+    println!("PGTEMP_TEST_PORT={}", db.db_port());
+    println!("PGTEMP_TEST_DATA_DIR={}", db.data_dir().display());
+
+    let mut buf = Vec::new();
+    std::io::stdin().read_to_end(&mut buf).unwrap();
+}
+
+/// Start a child process with a server and return it along with the server's port and data dir.
+/// The child gets its own process group, whose id is the child's pid.
+fn spawn_child(prefix: &std::path::Path, persist: bool, supervise: bool) -> (Child, u16, PathBuf) {
+    let mut cmd = Command::new(std::env::current_exe().unwrap());
+    cmd.args(["--ignored", "--exact", "gc_child", "--nocapture"])
+        .env("PGTEMP_TEST_PREFIX", prefix)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .process_group(0);
+    if persist {
+        cmd.env("PGTEMP_TEST_PERSIST", "1");
+    }
+    if supervise {
+        cmd.env("PGTEMP_TEST_SUPERVISE", "1");
+    }
+    let mut child = cmd.spawn().expect("failed to run child test process");
+
+    let stdout = child.stdout.take().unwrap();
+    let mut lines = BufReader::new(stdout).lines().map(Result::unwrap);
+    let port = lines
+        .find_map(|line| {
+            line.split_once("PGTEMP_TEST_PORT=")
+                .map(|(_, p)| p.to_string())
+        })
+        .expect("child did not print port")
+        .parse()
+        .unwrap();
+    let data_dir = lines
+        .find_map(|line| {
+            line.split_once("PGTEMP_TEST_DATA_DIR=")
+                .map(|(_, d)| PathBuf::from(d))
+        })
+        .expect("child did not print data dir");
+    std::thread::spawn(move || lines.for_each(drop));
+
+    (child, port, data_dir)
+}
+
+fn server_is_running(port: u16) -> bool {
+    std::net::TcpStream::connect(("127.0.0.1", port)).is_ok()
+}
+
+#[test]
+/// servers of killed processes are stopped and their temp dirs deleted
+fn gc_collects_killed_process() {
+    let prefix = tempfile::tempdir().unwrap();
+    let (mut child, port, data_dir) = spawn_child(prefix.path(), false, false);
+    let temp_dir = data_dir.parent().unwrap().to_path_buf();
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(server_is_running(port));
+    assert!(temp_dir.join("pgtemp-metadata").is_file());
+
+    let collected = pgtemp::gc_in(prefix.path());
+    assert_eq!(collected, vec![temp_dir.clone()]);
+    assert!(!server_is_running(port));
+    assert!(!temp_dir.exists());
+}
+
+#[test]
+/// servers whose owning process is still running are left alone
+fn gc_ignores_running_process() {
+    let prefix = tempfile::tempdir().unwrap();
+    let (mut child, port, data_dir) = spawn_child(prefix.path(), false, false);
+
+    assert!(pgtemp::gc_in(prefix.path()).is_empty());
+    assert!(server_is_running(port));
+    assert!(data_dir.exists());
+
+    drop(child.stdin.take());
+    assert!(child.wait().unwrap().success());
+    assert!(!server_is_running(port));
+    assert!(!data_dir.exists());
+}
+
+#[test]
+/// the data dirs of killed processes that persist their data are kept
+fn gc_keeps_persisted_data() {
+    let prefix = tempfile::tempdir().unwrap();
+    let (mut child, port, data_dir) = spawn_child(prefix.path(), true, false);
+    let temp_dir = data_dir.parent().unwrap().to_path_buf();
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let collected = pgtemp::gc_in(prefix.path());
+    assert_eq!(collected, vec![temp_dir.clone()]);
+    assert!(!server_is_running(port));
+    assert!(data_dir.exists());
+
+    // it is only collected once
+    assert!(pgtemp::gc_in(prefix.path()).is_empty());
+}
+
+#[test]
+/// the postmaster's pid is recorded, and a running server that doesn't match the recorded pid is
+/// neither stopped nor deleted
+fn gc_skips_unidentified_running_server() {
+    let prefix = tempfile::tempdir().unwrap();
+    let (mut child, port, data_dir) = spawn_child(prefix.path(), false, false);
+    let temp_dir = data_dir.parent().unwrap().to_path_buf();
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let metadata_path = temp_dir.join("pgtemp-metadata");
+    let metadata = std::fs::read_to_string(&metadata_path).unwrap();
+    let postmaster_pid = std::fs::read_to_string(data_dir.join("postmaster.pid")).unwrap();
+    let postmaster_pid = postmaster_pid.lines().next().unwrap();
+    assert!(metadata.contains(&format!("postgres_pid={}\n", postmaster_pid)));
+
+    let unidentified = metadata.replace(
+        &format!("postgres_pid={}\n", postmaster_pid),
+        "postgres_pid=1\n",
+    );
+    std::fs::write(&metadata_path, unidentified).unwrap();
+    assert!(pgtemp::gc_in(prefix.path()).is_empty());
+    assert!(server_is_running(port));
+    assert!(data_dir.exists());
+
+    std::fs::write(&metadata_path, metadata).unwrap();
+    assert_eq!(pgtemp::gc_in(prefix.path()), vec![temp_dir.clone()]);
+    assert!(!server_is_running(port));
+    assert!(!temp_dir.exists());
+}
+
+/// Wait up to 10 seconds for `condition` to become true
+fn wait_until(condition: impl Fn() -> bool) -> bool {
+    for _ in 0..100 {
+        if condition() {
+            return true;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    false
+}
+
+#[test]
+/// the supervisor stops the server and deletes its temp dir when the owning process is killed
+fn supervisor_cleans_up_killed_process() {
+    let prefix = tempfile::tempdir().unwrap();
+    let (mut child, port, data_dir) = spawn_child(prefix.path(), false, true);
+    let temp_dir = data_dir.parent().unwrap().to_path_buf();
+    assert!(server_is_running(port));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(wait_until(|| !server_is_running(port)));
+    assert!(wait_until(|| !temp_dir.exists()));
+}
+
+#[test]
+/// the supervisor also cleans up when its owner's whole process group is terminated, like e.g.
+/// `timeout` and CI runners do
+fn supervisor_survives_process_group_termination() {
+    let prefix = tempfile::tempdir().unwrap();
+    let (mut child, port, data_dir) = spawn_child(prefix.path(), false, true);
+    let temp_dir = data_dir.parent().unwrap().to_path_buf();
+
+    let status = Command::new("kill")
+        .args(["-TERM", "--", &format!("-{}", child.id())])
+        .status()
+        .expect("failed to run kill");
+    assert!(status.success());
+    child.wait().unwrap();
+
+    assert!(wait_until(|| !server_is_running(port)));
+    assert!(wait_until(|| !temp_dir.exists()));
+}
+
+#[test]
+/// the supervisor keeps the data dir when the data is persisted
+fn supervisor_keeps_persisted_data() {
+    let prefix = tempfile::tempdir().unwrap();
+    let (mut child, port, data_dir) = spawn_child(prefix.path(), true, true);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(wait_until(|| !server_is_running(port)));
+    assert!(data_dir.exists());
+}
+
+#[test]
+/// the supervisor doesn't interfere with a normal shutdown
+fn supervisor_released_on_shutdown() {
+    let prefix = tempfile::tempdir().unwrap();
+    let (mut child, port, data_dir) = spawn_child(prefix.path(), true, true);
+
+    drop(child.stdin.take());
+    assert!(child.wait().unwrap().success());
+    assert!(!server_is_running(port));
+    // the data dir is persisted, so it must not have been deleted by the supervisor
+    assert!(data_dir.exists());
+
+    // and the supervisor has exited
+    let temp_dir = data_dir.parent().unwrap();
+    let pgrep = Command::new("pgrep")
+        .args([
+            "-f",
+            &format!("pgtemp-supervisor .* {}", temp_dir.display()),
+        ])
+        .output()
+        .expect("failed to run pgrep");
+    assert!(pgrep.stdout.is_empty());
+}
@@ -0,0 +1,142 @@
+//! Tests for the typed locale, encoding and time settings
+
+use pgtemp::{DateOrder, DateOutputStyle, PgTempDBBuilder};
+use sqlx::postgres::PgConnection;
+use sqlx::prelude::*;
+
+#[tokio::test]
+/// the settings are applied to the database and server
+async fn locale_settings() {
+    let db = PgTempDBBuilder::new()
+        .with_encoding("LATIN1")
+        .with_lc_collate("C")
+        .with_lc_ctype("C")
+        .with_timezone("America/New_York")
+        .with_datestyle(DateOutputStyle::Sql, DateOrder::Dmy)
+        .start_async()
+        .await;
+
+    let mut conn = PgConnection::connect(&db.connection_uri())
+        .await
+        .expect("failed to connect to db");
+    let row = sqlx::query(
+        "SELECT pg_encoding_to_char(encoding), datcollate, datctype \
+         FROM pg_database WHERE datname = current_database()",
+    )
+    .fetch_one(&mut conn)
+    .await
+    .expect("failed to execute query");
+    let encoding: &str = row.get(0);
+    let collate: &str = row.get(1);
+    let ctype: &str = row.get(2);
+    assert_eq!(encoding, "LATIN1");
+    assert_eq!(collate, "C");
+    assert_eq!(ctype, "C");
+
+    // sqlx overrides TimeZone and DateStyle for its sessions, so check the server's settings with
+    // psql
+    let output = std::process::Command::new("psql")
+        .arg(db.connection_uri())
+        .args(["--no-psqlrc", "--tuples-only", "--no-align"])
+        .args([
+            "--command",
+            "SELECT current_setting('TimeZone'), current_setting('DateStyle')",
+        ])
+        .env_remove("PGTZ")
+        .env_remove("PGDATESTYLE")
+        .output()
+        .expect("failed to run psql");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "America/New_York|SQL, DMY"
+    );
+}
+
+#[tokio::test]
+/// the ICU locale provider is used for the database's collation
+async fn icu_locale() {
+    let db = PgTempDBBuilder::new()
+        .with_encoding("UTF8")
+        .with_lc_collate("C.UTF-8")
+        .with_lc_ctype("C.UTF-8")
+        .with_icu_locale("de-DE")
+        .start_async()
+        .await;
+
+    let mut conn = PgConnection::connect(&db.connection_uri())
+        .await
+        .expect("failed to connect to db");
+    // ICU sorts case-insensitively first, unlike the C locale
+    let rows = sqlx::query("SELECT x FROM (VALUES ('b'), ('A'), ('a'), ('B')) v(x) ORDER BY x")
+        .fetch_all(&mut conn)
+        .await
+        .expect("failed to execute query");
+    let sorted: Vec<&str> = rows.iter().map(|row| row.get(0)).collect();
+    assert_eq!(sorted, ["a", "A", "b", "B"]);
+}
+
+#[test]
+#[should_panic(expected = "locale `xx_XX.UTF-8` is not available on this system")]
+/// unknown locales are reported before running initdb
+fn unknown_locale() {
+    let _db = PgTempDBBuilder::new()
+        .with_lc_collate("xx_XX.UTF-8")
+        .start();
+}
+
+#[test]
+#[should_panic(expected = "`UTF16` is not a PostgreSQL server encoding")]
+/// unknown encodings are reported before running initdb
+fn unknown_encoding() {
+    let _db = PgTempDBBuilder::new().with_encoding("UTF16").start();
+}
+
+#[test]
+#[should_panic(expected = "`Mars/Olympus_Mons` is not a known time zone")]
+/// unknown time zones are reported before starting the server
+fn unknown_timezone() {
+    let _db = PgTempDBBuilder::new()
+        .with_timezone("Mars/Olympus_Mons")
+        .start();
+}
+
+#[test]
+#[should_panic(expected = "`Berlin` is not a known time zone")]
+/// zone names without a region are checked too
+fn unknown_timezone_without_region() {
+    let _db = PgTempDBBuilder::new().with_timezone("Berlin").start();
+}
+
+#[test]
+/// postgres matches zone names case-insensitively
+fn timezone_case_insensitive() {
+    let db = PgTempDBBuilder::new()
+        .with_timezone("europe/berlin")
+        .start();
+    assert_eq!(
+        db.query("SELECT current_setting('TimeZone')"),
+        [[Some("Europe/Berlin".to_string())]]
+    );
+}
+
+#[test]
+/// POSIX time zone specs aren't zone names, but postgres accepts them
+fn posix_timezone_spec() {
+    for timezone in ["<+03>-3", "UTC+3"] {
+        let db = PgTempDBBuilder::new().with_timezone(timezone).start();
+        assert_eq!(
+            db.query("SELECT current_setting('TimeZone')"),
+            [[Some(timezone.to_string())]]
+        );
+    }
+}
+
+#[test]
+#[should_panic(expected = "the ICU locale provider requires an ICU locale")]
+/// the ICU provider can't be used without a locale
+fn icu_provider_without_locale() {
+    let mut builder = PgTempDBBuilder::new();
+    builder.locale_provider = Some(pgtemp::LocaleProvider::Icu);
+    let _db = builder.start();
+}
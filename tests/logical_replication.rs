@@ -0,0 +1,77 @@
+//! Tests for logical replication between PgTempDBs
+
+use pgtemp::{PgTempDB, PgTempDBBuilder};
+
+fn start_publisher() -> PgTempDB {
+    let publisher = PgTempDBBuilder::new().with_logical_replication().start();
+    publisher.execute(
+        "CREATE TABLE items (id serial PRIMARY KEY, name text NOT NULL);
+         CREATE TABLE unpublished (id int PRIMARY KEY);
+         INSERT INTO items (name) VALUES ('a'), ('b');",
+    );
+    publisher.create_publication("items_pub", &["items"]);
+    publisher
+}
+
+fn names(db: &PgTempDB) -> Vec<Vec<Option<String>>> {
+    db.query("SELECT name FROM items ORDER BY id")
+}
+
+fn slot_count(publisher: &PgTempDB) -> String {
+    publisher.query("SELECT count(*) FROM pg_replication_slots")[0][0]
+        .clone()
+        .unwrap()
+}
+
+#[test]
+/// a subscriber gets the published tables' schema and rows, and then their changes
+fn subscriber_copies_and_streams() {
+    let publisher = start_publisher();
+    let subscriber = PgTempDBBuilder::new().start_subscriber(&publisher, "items_pub");
+
+    assert_eq!(
+        names(&subscriber),
+        [[Some("a".to_string())], [Some("b".to_string())]]
+    );
+    // only the published table is created
+    assert_eq!(
+        subscriber.query("SELECT to_regclass('unpublished')"),
+        [[None]]
+    );
+
+    publisher.execute("INSERT INTO items (name) VALUES ('c'); DELETE FROM items WHERE name = 'a'");
+    subscriber.wait_for_subscription_sync(&publisher);
+    assert_eq!(
+        names(&subscriber),
+        [[Some("b".to_string())], [Some("c".to_string())]]
+    );
+}
+
+#[test]
+/// subscribing into an existing table, and dropping the subscriber removes its replication slot
+/// from the publisher
+fn subscription_dropped_on_shutdown() {
+    let publisher = start_publisher();
+    let subscriber = PgTempDB::new();
+    subscriber.execute("CREATE TABLE items (id int PRIMARY KEY, name text, extra text)");
+    subscriber.create_subscription("items_sub", &publisher, "items_pub");
+    subscriber.wait_for_subscription_sync(&publisher);
+    assert_eq!(
+        names(&subscriber),
+        [[Some("a".to_string())], [Some("b".to_string())]]
+    );
+    assert_eq!(slot_count(&publisher), "1");
+
+    subscriber.shutdown();
+    assert_eq!(slot_count(&publisher), "0");
+}
+
+#[test]
+/// shutting down the publisher before the subscriber doesn't hang or fail
+fn publisher_shut_down_first() {
+    let publisher = start_publisher();
+    let subscriber = PgTempDBBuilder::new().start_subscriber(&publisher, "items_pub");
+
+    publisher.shutdown();
+    subscriber.shutdown();
+}
@@ -0,0 +1,115 @@
+//! Tests for the `#[pgtemp::test]` attribute macro
+#![cfg(feature = "macros")]
+
+use pgtemp::PgTempDB;
+use sqlx::postgres::PgConnection;
+use sqlx::prelude::*;
+
+/// whether a postgres server was started with `marker` in its command line
+fn server_running_with(marker: &str) -> bool {
+    std::fs::read_dir("/proc")
+        .expect("failed to read /proc")
+        .filter_map(Result::ok)
+        .filter_map(|entry| std::fs::read(entry.path().join("cmdline")).ok())
+        .any(|cmdline| {
+            cmdline
+                .split(|&b| b == 0)
+                .any(|arg| arg == marker.as_bytes())
+        })
+}
+
+#[pgtemp::test(config(cluster_name = "pgtemp_sync_no_args"))]
+/// no arguments, just starts a database
+fn sync_no_args() {
+    assert!(server_running_with("cluster_name=pgtemp_sync_no_args"));
+}
+
+#[pgtemp::test(dbname = "macrodb")]
+/// sync test with a borrowed database
+fn sync_borrowed_db(db: &PgTempDB) {
+    assert_eq!(db.db_name(), "macrodb");
+    assert!(db.data_dir().exists());
+}
+
+#[pgtemp::test]
+/// sync test that owns the database
+fn sync_owned_db(db: PgTempDB) {
+    let data_dir = db.data_dir();
+    db.shutdown();
+    assert!(!data_dir.exists());
+}
+
+#[pgtemp::test(config(max_connections = "77", geqo = false))]
+/// async test with config params
+async fn async_config_params(db: &PgTempDB) {
+    let mut conn = PgConnection::connect(&db.connection_uri())
+        .await
+        .expect("failed to connect to db");
+
+    let row = sqlx::query("SELECT setting FROM pg_settings WHERE name = 'max_connections'")
+        .fetch_one(&mut conn)
+        .await
+        .expect("failed to get max_connections");
+    let max_connections: &str = row.get(0);
+    assert_eq!(max_connections, "77");
+
+    let row = sqlx::query("SHOW geqo")
+        .fetch_one(&mut conn)
+        .await
+        .expect("failed to get geqo");
+    let geqo: &str = row.get(0);
+    assert_eq!(geqo, "off");
+}
+
+#[pgtemp::test(load = "tests/fixtures/person.sql")]
+/// async test with the connection uri and a loaded script
+async fn async_load_uri(uri: String) {
+    let mut conn = PgConnection::connect(&uri)
+        .await
+        .expect("failed to connect to db");
+
+    let row = sqlx::query("SELECT name FROM person")
+        .fetch_one(&mut conn)
+        .await
+        .expect("failed to select names from person");
+    let name: &str = row.get(0);
+    assert_eq!(name, "example name");
+}
+
+#[pgtemp::test(migrations = "examples/sqlx-migrations")]
+/// async test with a borrowed connection uri, migrations, and a return value
+async fn async_migrations_result(uri: &str) -> Result<(), sqlx::Error> {
+    let mut conn = PgConnection::connect(uri).await?;
+
+    sqlx::query("INSERT INTO tasks (task) VALUES ('test')")
+        .execute(&mut conn)
+        .await?;
+    let count: i64 = sqlx::query("SELECT count(*) FROM tasks")
+        .fetch_one(&mut conn)
+        .await?
+        .get(0);
+    assert_eq!(count, 1);
+
+    Ok(())
+}
+
+#[pgtemp::test(migrations = "examples/diesel-migrations")]
+/// diesel-style migrations work too
+fn diesel_migrations(db: &PgTempDB) {
+    assert_eq!(
+        db.query("SELECT count(*) FROM tasks"),
+        [[Some("0".to_string())]]
+    );
+    // diesel's own setup migration ran as well
+    assert_eq!(
+        db.query("SELECT proname::text FROM pg_proc WHERE proname = 'diesel_manage_updated_at'"),
+        [[Some("diesel_manage_updated_at".to_string())]]
+    );
+}
+
+#[pgtemp::test]
+#[should_panic(expected = "oh no")]
+/// panics in async tests are propagated after the database is shut down
+async fn async_panic(_db: &PgTempDB) {
+    panic!("oh no");
+}
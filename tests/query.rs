@@ -0,0 +1,77 @@
+//! Tests for running SQL without a database driver
+
+use pgtemp::PgTempDB;
+
+fn some(value: &str) -> Option<String> {
+    Some(value.to_string())
+}
+
+#[test]
+/// statements can be executed and queried
+fn execute_and_query() {
+    let db = PgTempDB::new();
+    db.execute(
+        "CREATE TABLE items (id int, name text, note text);
+         INSERT INTO items VALUES (1, 'a', NULL), (2, '', 'two\nlines'), (3, 'x,\"y\"', 'z');",
+    );
+
+    let rows = db.query("SELECT id, name, note FROM items ORDER BY id");
+    assert_eq!(
+        rows,
+        [
+            [some("1"), some("a"), None],
+            [some("2"), some(""), some("two\nlines")],
+            [some("3"), some("x,\"y\""), some("z")],
+        ]
+    );
+
+    // an empty result has no rows
+    assert!(db.query("SELECT * FROM items WHERE false").is_empty());
+    // values use postgres's text format
+    assert_eq!(
+        db.query("SELECT true, 1.50::numeric, ARRAY[1, 2]"),
+        [[some("t"), some("1.50"), some("{1,2}")]]
+    );
+    // non-ASCII text is returned intact
+    assert_eq!(db.query("SELECT 'héllo ☃'"), [[some("héllo ☃")]]);
+    // a single empty string
+    assert_eq!(db.query("SELECT ''"), [[some("")]]);
+    // escaped characters and DML with RETURNING
+    assert_eq!(
+        db.query(
+            "INSERT INTO items VALUES (4, E'tab\\there', E'back\\\\slash') RETURNING name, note;"
+        ),
+        [[some("tab\there"), some("back\\slash")]]
+    );
+}
+
+#[test]
+/// query results can be returned as CSV and JSON
+fn query_csv_and_json() {
+    let db = PgTempDB::new();
+    db.execute(
+        "CREATE TABLE items (id int, name text); INSERT INTO items VALUES (1, 'a,b'), (2, NULL);",
+    );
+
+    let csv = db.query_csv("SELECT id, name FROM items ORDER BY id");
+    assert_eq!(csv, "id,name\n1,\"a,b\"\n2,\n");
+    let csv = db.query_csv("SELECT '' AS empty, NULL AS null");
+    assert_eq!(csv, "empty,null\n\"\",\n");
+
+    let json = db.query_json("SELECT id, name FROM items ORDER BY id;");
+    assert_eq!(
+        json,
+        "[{\"id\":1,\"name\":\"a,b\"}, \n {\"id\":2,\"name\":null}]"
+    );
+
+    let json = db.query_json("SELECT * FROM items WHERE false -- no rows");
+    assert_eq!(json, "[]");
+}
+
+#[test]
+#[should_panic(expected = "relation \"missing\" does not exist")]
+/// errors panic with psql's output
+fn query_error() {
+    let db = PgTempDB::new();
+    let _rows = db.query("SELECT * FROM missing");
+}
@@ -0,0 +1,85 @@
+//! Tests for PgTempReplicaSet
+
+use pgtemp::{PgTempDBBuilder, PgTempReplicaSet};
+
+fn count_rows(db: &pgtemp::PgTempDB) -> String {
+    db.query("SELECT count(*) FROM items")[0][0]
+        .clone()
+        .unwrap()
+}
+
+#[test]
+/// changes on the primary are replayed on the replicas, which are read-only
+fn replicas_stream_from_primary() {
+    let set = PgTempDBBuilder::new()
+        .with_dbname("replicated")
+        .start_replica_set(2);
+    assert_eq!(set.replicas().len(), 2);
+    assert_ne!(set.primary().db_port(), set.replica(0).db_port());
+    assert_ne!(set.replica(0).db_port(), set.replica(1).db_port());
+
+    set.primary()
+        .execute("CREATE TABLE items (id int); INSERT INTO items VALUES (1), (2)");
+    set.wait_for_replay();
+    for replica in set.replicas() {
+        assert_eq!(replica.db_name(), "replicated");
+        assert_eq!(count_rows(replica), "2");
+        assert_eq!(
+            replica.query("SELECT pg_is_in_recovery()"),
+            [[Some("t".to_string())]]
+        );
+        assert_eq!(
+            replica.query("SELECT current_setting('transaction_read_only')"),
+            [[Some("on".to_string())]]
+        );
+    }
+}
+
+#[test]
+/// replicas added later start from the primary's current state
+fn add_replica_later() {
+    let mut set = PgTempReplicaSet::from_builder(PgTempDBBuilder::new(), 0);
+    assert!(set.replicas().is_empty());
+    set.primary()
+        .execute("CREATE TABLE items (id int); INSERT INTO items VALUES (1)");
+
+    let replica = set.add_replica();
+    assert_eq!(count_rows(replica), "1");
+}
+
+#[test]
+/// after promoting a replica, it accepts writes and the remaining replicas follow it
+fn promote_replica() {
+    let mut set = PgTempDBBuilder::new().start_replica_set(2);
+    set.primary()
+        .execute("CREATE TABLE items (id int); INSERT INTO items VALUES (1)");
+    set.wait_for_replay();
+    let old_primary_port = set.primary().db_port();
+    let promoted_port = set.replica(1).db_port();
+
+    set.promote(1);
+    assert_eq!(set.primary().db_port(), promoted_port);
+    assert_eq!(set.replicas().len(), 1);
+    assert_ne!(set.replica(0).db_port(), old_primary_port);
+    assert_eq!(
+        set.primary().query("SELECT pg_is_in_recovery()"),
+        [[Some("f".to_string())]]
+    );
+
+    set.primary().execute("INSERT INTO items VALUES (2)");
+    set.wait_for_replay();
+    assert_eq!(count_rows(set.replica(0)), "2");
+}
+
+#[test]
+/// replication works when the primary requires passwords
+fn replicas_with_password_auth() {
+    let set = PgTempDBBuilder::new()
+        .require_password_auth()
+        .with_random_password()
+        .start_replica_set(1);
+    set.primary()
+        .execute("CREATE TABLE items (id int); INSERT INTO items VALUES (1)");
+    set.wait_for_replay();
+    assert_eq!(count_rows(set.replica(0)), "1");
+}
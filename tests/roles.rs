@@ -0,0 +1,75 @@
+//! Tests for creating roles on startup
+
+use pgtemp::{PgTempDBBuilder, RoleAttribute};
+use sqlx::postgres::PgConnection;
+use sqlx::prelude::*;
+
+#[tokio::test]
+/// roles are created before the load script runs, and can log in with their password
+async fn roles_created_on_startup() {
+    let temp = tempfile::tempdir().unwrap();
+    let load_path = temp.path().join("schema.sql");
+    std::fs::write(
+        &load_path,
+        "CREATE TABLE person (id SERIAL PRIMARY KEY, name TEXT NOT NULL);
+         INSERT INTO person (name) VALUES ('example');
+         GRANT SELECT ON person TO readers;",
+    )
+    .unwrap();
+
+    let db = PgTempDBBuilder::new()
+        .with_role("readers", "unused", &[RoleAttribute::NoLogin])
+        .with_role(
+            "app",
+            "app'pass",
+            &[
+                RoleAttribute::CreateDb,
+                RoleAttribute::NoSuperuser,
+                RoleAttribute::InRole("readers".into()),
+            ],
+        )
+        .require_password_auth()
+        .load_database(&load_path)
+        .start_async()
+        .await;
+
+    let mut conn = PgConnection::connect(&db.connection_uri_for("app"))
+        .await
+        .expect("failed to connect as app");
+    let row = sqlx::query(
+        "SELECT current_user, rolcreatedb, rolsuper FROM pg_roles WHERE rolname = current_user",
+    )
+    .fetch_one(&mut conn)
+    .await
+    .expect("failed to execute query");
+    let user: &str = row.get(0);
+    let createdb: bool = row.get(1);
+    let superuser: bool = row.get(2);
+    assert_eq!(user, "app");
+    assert!(createdb);
+    assert!(!superuser);
+
+    // privileges come from membership in readers
+    let row = sqlx::query("SELECT name FROM person")
+        .fetch_one(&mut conn)
+        .await
+        .expect("failed to select");
+    let name: &str = row.get(0);
+    assert_eq!(name, "example");
+    let res = sqlx::query("INSERT INTO person (name) VALUES ('other')")
+        .execute(&mut conn)
+        .await;
+    assert!(res.is_err());
+
+    // the group role can't log in
+    let readers_uri = db.connection_uri_for("readers");
+    assert!(PgConnection::connect(&readers_uri).await.is_err());
+}
+
+#[test]
+#[should_panic(expected = "role `missing` was not created")]
+/// unknown roles panic
+fn connection_uri_for_unknown_role() {
+    let db = PgTempDBBuilder::new().start();
+    let _uri = db.connection_uri_for("missing");
+}
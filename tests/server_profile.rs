@@ -0,0 +1,118 @@
+//! Tests for server configuration presets
+
+use pgtemp::{PgTempDBBuilder, ServerProfile};
+use sqlx::postgres::PgConnection;
+use sqlx::prelude::*;
+
+/// Get the values of the given settings on the server
+async fn settings(builder: PgTempDBBuilder, names: &[&str]) -> Vec<String> {
+    let db = builder.start_async().await;
+    let mut conn = PgConnection::connect(&db.connection_uri())
+        .await
+        .expect("failed to connect to db");
+
+    let mut values = Vec::new();
+    for name in names {
+        let row = sqlx::query("SELECT current_setting($1)")
+            .bind(name)
+            .fetch_one(&mut conn)
+            .await
+            .expect("failed to execute query");
+        values.push(row.get(0));
+    }
+    values
+}
+
+#[tokio::test]
+/// the fast profile is used by default and turns off durability
+async fn default_profile_is_fast() {
+    let names = [
+        "fsync",
+        "synchronous_commit",
+        "full_page_writes",
+        "autovacuum",
+    ];
+    let values = settings(PgTempDBBuilder::new(), &names).await;
+    assert_eq!(values, ["off", "off", "off", "off"]);
+}
+
+#[tokio::test]
+/// the production-like profile keeps durability and autovacuum on
+async fn production_like_profile() {
+    let builder = PgTempDBBuilder::new().with_server_profile(ServerProfile::ProductionLike);
+    let names = [
+        "fsync",
+        "synchronous_commit",
+        "full_page_writes",
+        "autovacuum",
+    ];
+    let values = settings(builder, &names).await;
+    assert_eq!(values, ["on", "on", "on", "on"]);
+}
+
+#[tokio::test]
+/// the minimal profile shrinks the server's memory and connection limits
+async fn minimal_profile() {
+    let builder = PgTempDBBuilder::new().with_server_profile(ServerProfile::Minimal);
+    let values = settings(builder, &["shared_buffers", "max_connections", "fsync"]).await;
+    assert_eq!(values, ["1MB", "10", "off"]);
+}
+
+#[tokio::test]
+/// the debug profile logs every statement
+async fn debug_profile() {
+    let builder = PgTempDBBuilder::new().with_server_profile(ServerProfile::Debug);
+    let values = settings(builder, &["log_statement", "log_connections"]).await;
+    assert_eq!(values, ["all", "on"]);
+}
+
+#[test]
+/// the debug profile's output doesn't fill up the pipe and block the server
+fn debug_profile_output_is_drained() {
+    let db = PgTempDBBuilder::new()
+        .with_server_profile(ServerProfile::Debug)
+        .start();
+    // each statement is logged, so this writes well over a pipe buffer's worth of output
+    let sql = format!("SELECT '{}'", "x".repeat(1000));
+    for _ in 0..200 {
+        db.execute(&sql);
+    }
+}
+
+#[tokio::test]
+/// without presets, the server uses postgres's defaults
+async fn postgres_defaults_profile() {
+    let builder = PgTempDBBuilder::new().with_server_profile(ServerProfile::PostgresDefaults);
+    let values = settings(builder, &["fsync", "autovacuum"]).await;
+    assert_eq!(values, ["on", "on"]);
+}
+
+#[tokio::test]
+/// explicitly configured parameters take precedence over the profile's
+async fn config_params_override_profile() {
+    let builder = PgTempDBBuilder::new()
+        .with_server_profile(ServerProfile::Minimal)
+        .with_config_param("MAX_CONNECTIONS", "20")
+        .with_config_param("autovacuum", "on");
+    let values = settings(builder, &["max_connections", "autovacuum", "fsync"]).await;
+    assert_eq!(values, ["20", "on", "off"]);
+}
+
+#[test]
+/// profiles can be parsed from their names
+fn parse_profile() {
+    for profile in [
+        ServerProfile::Fast,
+        ServerProfile::ProductionLike,
+        ServerProfile::Minimal,
+        ServerProfile::Debug,
+        ServerProfile::PostgresDefaults,
+    ] {
+        assert_eq!(profile.to_string().parse::<ServerProfile>(), Ok(profile));
+    }
+    assert_eq!(
+        "production-like".parse::<ServerProfile>(),
+        Ok(ServerProfile::ProductionLike)
+    );
+    assert!("turbo".parse::<ServerProfile>().is_err());
+}
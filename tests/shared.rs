@@ -0,0 +1,77 @@
+//! Tests for the process-wide shared server
+
+use pgtemp::PgTempDBBuilder;
+use sqlx::postgres::PgConnection;
+use sqlx::prelude::*;
+
+#[tokio::test]
+/// databases from the shared server are on the same server but are independent
+async fn shared_databases_are_independent() {
+    let db1 = pgtemp::shared();
+    let db2 = pgtemp::shared();
+    assert_eq!(db1.db_port(), db2.db_port());
+    assert_ne!(db1.db_name(), db2.db_name());
+
+    let mut conn1 = PgConnection::connect(&db1.connection_uri())
+        .await
+        .expect("failed to connect to db1");
+    let mut conn2 = PgConnection::connect(&db2.connection_uri())
+        .await
+        .expect("failed to connect to db2");
+
+    sqlx::query("CREATE TABLE person (id SERIAL PRIMARY KEY, name TEXT NOT NULL)")
+        .execute(&mut conn1)
+        .await
+        .expect("failed to create table");
+
+    let res = sqlx::query("SELECT * FROM person")
+        .fetch_all(&mut conn2)
+        .await;
+    assert!(res.is_err());
+}
+
+#[test]
+/// different builder configurations get different servers
+fn shared_with_config() {
+    let builder = PgTempDBBuilder::new().with_config_param("geqo", "off");
+    let db1 = pgtemp::shared_with(&builder);
+    let db2 = pgtemp::shared_with(&builder);
+    let db3 = pgtemp::shared_with(&PgTempDBBuilder::new().with_config_param("jit", "off"));
+
+    assert_eq!(db1.db_port(), db2.db_port());
+    assert_ne!(db1.db_port(), db3.db_port());
+}
+
+#[test]
+#[ignore = "run by shared_server_stopped_at_exit in a separate process"]
+/// prints the port of the shared server and exits
+fn shared_child_process() {
+    let db = pgtemp::shared();
+    println!("PGTEMP_SHARED_PORT={}", db.db_port());
+}
+
+#[test]
+/// the shared server is shut down when the process exits
+fn shared_server_stopped_at_exit() {
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args([
+            "--ignored",
+            "--exact",
+            "shared_child_process",
+            "--nocapture",
+        ])
+        .output()
+        .expect("failed to run child test process");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let port: u16 = stdout
+        .lines()
+        .find_map(|line| line.split_once("PGTEMP_SHARED_PORT="))
+        .expect("child did not print port")
+        .1
+        .parse()
+        .unwrap();
+
+    assert!(std::net::TcpStream::connect(("127.0.0.1", port)).is_err());
+}
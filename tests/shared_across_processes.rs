@@ -0,0 +1,134 @@
+//! Tests for sharing a server between processes
+
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+use pgtemp::PgTempDBBuilder;
+
+/// each test uses its own configuration so that they don't share servers with each other
+fn builder(name: &str) -> PgTempDBBuilder {
+    PgTempDBBuilder::new().with_config_param("application_name", name)
+}
+
+#[test]
+#[ignore = "run by the other tests in this file in separate processes"]
+/// attaches to the shared server, prints its port, and then waits for stdin to be closed
+fn cross_process_child() {
+    let name = std::env::var("PGTEMP_TEST_NAME").unwrap();
+    let db = pgtemp::shared_across_processes_with(&builder(&name));
+    println!("PGTEMP_SHARED_PORT={}", db.db_port());
+
+    let mut buf = Vec::new();
+    std::io::stdin().read_to_end(&mut buf).unwrap();
+}
+
+/// Start a child process that attaches to the shared server and return it along with the server's
+/// port
+fn spawn_child(name: &str) -> (Child, u16) {
+    spawn_child_in(name, None)
+}
+
+/// Like [`spawn_child`], with the coordination directory in `runtime_dir` if given
+fn spawn_child_in(name: &str, runtime_dir: Option<&Path>) -> (Child, u16) {
+    let mut cmd = Command::new(std::env::current_exe().unwrap());
+    cmd.args(["--ignored", "--exact", "cross_process_child", "--nocapture"])
+        .env("PGTEMP_TEST_NAME", name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+    if let Some(runtime_dir) = runtime_dir {
+        cmd.env("XDG_RUNTIME_DIR", runtime_dir);
+    }
+    let mut child = cmd.spawn().expect("failed to run child test process");
+
+    let stdout: ChildStdout = child.stdout.take().unwrap();
+    let mut lines = BufReader::new(stdout).lines().map(Result::unwrap);
+    let port = lines
+        .find_map(|line| {
+            line.split_once("PGTEMP_SHARED_PORT=")
+                .map(|(_, p)| p.to_string())
+        })
+        .expect("child did not print port")
+        .parse()
+        .unwrap();
+    // keep reading so the child doesn't get a broken pipe
+    std::thread::spawn(move || lines.for_each(drop));
+
+    (child, port)
+}
+
+fn server_is_running(port: u16) -> bool {
+    std::net::TcpStream::connect(("127.0.0.1", port)).is_ok()
+}
+
+#[test]
+/// the server is shared by all attached processes and stopped when the last one exits
+fn shared_between_processes() {
+    let (mut child1, port1) = spawn_child("shared_between_processes");
+    let (mut child2, port2) = spawn_child("shared_between_processes");
+    assert_eq!(port1, port2);
+
+    // closing stdin makes the child exit
+    drop(child1.stdin.take());
+    assert!(child1.wait().unwrap().success());
+    assert!(server_is_running(port1));
+
+    drop(child2.stdin.take());
+    assert!(child2.wait().unwrap().success());
+    assert!(!server_is_running(port1));
+}
+
+#[test]
+/// a process that was killed without detaching doesn't keep the server alive
+fn killed_process_is_ignored() {
+    let (mut killed, port1) = spawn_child("killed_process_is_ignored");
+    killed.kill().unwrap();
+    killed.wait().unwrap();
+    assert!(server_is_running(port1));
+
+    let (mut child, port2) = spawn_child("killed_process_is_ignored");
+    assert_eq!(port1, port2);
+
+    drop(child.stdin.take());
+    assert!(child.wait().unwrap().success());
+    assert!(!server_is_running(port1));
+}
+
+#[test]
+/// databases in the same process are on the same server
+fn shared_in_process() {
+    let builder = builder("shared_in_process");
+    let db1 = pgtemp::shared_across_processes_with(&builder);
+    let db2 = pgtemp::shared_across_processes_with(&builder);
+    assert_eq!(db1.db_port(), db2.db_port());
+    assert_ne!(db1.db_name(), db2.db_name());
+}
+
+#[test]
+/// the coordination directory and the server state, which includes the password, are only
+/// accessible by the current user
+fn coordination_files_are_private() {
+    let runtime_dir = tempfile::tempdir().unwrap();
+    let (mut child, _port) =
+        spawn_child_in("coordination_files_are_private", Some(runtime_dir.path()));
+
+    let coordination_dir = runtime_dir
+        .path()
+        .read_dir()
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            path.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("pgtemp-shared-")
+        })
+        .expect("no coordination directory");
+    let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(&coordination_dir), 0o700);
+    assert_eq!(mode(&coordination_dir.join("server")), 0o600);
+
+    drop(child.stdin.take());
+    assert!(child.wait().unwrap().success());
+}
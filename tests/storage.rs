@@ -0,0 +1,50 @@
+//! Tests for memory-backed data directories
+
+use pgtemp::PgTempDBBuilder;
+use sqlx::postgres::PgConnection;
+use sqlx::prelude::*;
+
+/// Whether `/dev/shm` is mounted as a tmpfs, according to `/proc/mounts`
+fn dev_shm_is_tmpfs() -> bool {
+    std::fs::read_to_string("/proc/mounts").is_ok_and(|mounts| {
+        mounts.lines().any(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            fields.get(1) == Some(&"/dev/shm") && fields.get(2) == Some(&"tmpfs")
+        })
+    })
+}
+
+#[tokio::test]
+/// the data directory is placed on /dev/shm when it is a tmpfs
+async fn memory_backed_storage() {
+    let db = PgTempDBBuilder::new()
+        .with_memory_backed_storage()
+        .start_async()
+        .await;
+
+    if dev_shm_is_tmpfs() {
+        assert!(db.data_dir().starts_with("/dev/shm"));
+    }
+
+    let mut conn = PgConnection::connect(&db.connection_uri())
+        .await
+        .expect("failed to connect to db");
+    let row = sqlx::query("SELECT 1")
+        .fetch_one(&mut conn)
+        .await
+        .expect("failed to execute query");
+    let one: i32 = row.get(0);
+    assert_eq!(one, 1);
+}
+
+#[test]
+/// an explicit data dir prefix takes precedence over memory-backed storage
+fn data_dir_prefix_takes_precedence() {
+    let temp = tempfile::tempdir().unwrap();
+    let db = PgTempDBBuilder::new()
+        .with_memory_backed_storage()
+        .with_data_dir_prefix(temp.path())
+        .start();
+
+    assert!(db.data_dir().starts_with(temp.path()));
+}
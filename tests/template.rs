@@ -0,0 +1,127 @@
+//! Tests for template databases
+
+use pgtemp::PgTempDB;
+use sqlx::postgres::{PgConnectOptions, PgConnection};
+use sqlx::prelude::*;
+
+#[tokio::test]
+/// clone a database with a table in it and check the clones are independent of each other
+async fn create_databases_from_template() {
+    let db = PgTempDB::builder()
+        .with_dbname("baseline")
+        .start_async()
+        .await;
+
+    let mut conn = PgConnection::connect(&db.connection_uri())
+        .await
+        .expect("failed to connect to db");
+    sqlx::query("CREATE TABLE person (id SERIAL PRIMARY KEY, name TEXT NOT NULL)")
+        .execute(&mut conn)
+        .await
+        .expect("failed to create table");
+    sqlx::query("INSERT INTO person (name) VALUES ('baseline')")
+        .execute(&mut conn)
+        .await
+        .expect("failed to insert name into values");
+    // postgres won't copy a database with open connections
+    conn.close().await.expect("failed to close connection");
+
+    db.mark_as_template();
+
+    let clone1 = db.create_database_from_template("clone1");
+    let clone2 = db.create_database_from_template("clone2");
+    assert_eq!(clone1.db_name(), "clone1");
+    assert_eq!(clone1.db_port(), db.db_port());
+
+    let mut conn1 = PgConnection::connect(&clone1.connection_uri())
+        .await
+        .expect("failed to connect to clone1");
+    let mut conn2 = PgConnection::connect(&clone2.connection_uri())
+        .await
+        .expect("failed to connect to clone2");
+
+    sqlx::query("INSERT INTO person (name) VALUES ('clone1')")
+        .execute(&mut conn1)
+        .await
+        .expect("failed to insert name into values");
+
+    let count1: i64 = sqlx::query("SELECT count(*) FROM person")
+        .fetch_one(&mut conn1)
+        .await
+        .expect("failed to count rows")
+        .get(0);
+    let count2: i64 = sqlx::query("SELECT count(*) FROM person")
+        .fetch_one(&mut conn2)
+        .await
+        .expect("failed to count rows")
+        .get(0);
+    assert_eq!(count1, 2);
+    assert_eq!(count2, 1);
+
+    // dropping the handle drops the database, even with an open connection
+    drop(clone1);
+
+    let rows = sqlx::query("SELECT datname FROM pg_database WHERE datname LIKE 'clone%'")
+        .fetch_all(&mut conn2)
+        .await
+        .expect("failed to list databases");
+    assert_eq!(rows.len(), 1);
+    let name: &str = rows[0].get(0);
+    assert_eq!(name, "clone2");
+}
+
+#[tokio::test]
+/// the default `postgres` database can be used as a template as well
+async fn template_default_database() {
+    let db = PgTempDB::new();
+    let mut conn = PgConnection::connect(&db.connection_uri())
+        .await
+        .expect("failed to connect to db");
+    sqlx::query("CREATE TABLE person (id SERIAL PRIMARY KEY, name TEXT NOT NULL)")
+        .execute(&mut conn)
+        .await
+        .expect("failed to create table");
+    sqlx::query("INSERT INTO person (name) VALUES ('baseline')")
+        .execute(&mut conn)
+        .await
+        .expect("failed to insert name into values");
+    conn.close().await.expect("failed to close connection");
+
+    db.mark_as_template();
+
+    let clone = db.create_database_from_template("my clone");
+    assert_eq!(clone.db_name(), "my clone");
+
+    // sqlx doesn't percent-decode the database name in URIs
+    let options = PgConnectOptions::new()
+        .port(clone.db_port())
+        .username(clone.db_user())
+        .password(clone.db_pass())
+        .database(clone.db_name());
+    let mut clone_conn = PgConnection::connect_with(&options)
+        .await
+        .expect("failed to connect to clone");
+    let name: String = sqlx::query("SELECT name FROM person")
+        .fetch_one(&mut clone_conn)
+        .await
+        .expect("failed to select name")
+        .get(0);
+    assert_eq!(name, "baseline");
+}
+
+#[test]
+/// creating a database whose name is taken fails without dropping the existing database
+fn template_name_taken() {
+    let db = PgTempDB::new();
+    db.execute("CREATE DATABASE keep");
+    db.mark_as_template();
+
+    let res = std::thread::scope(|s| s.spawn(|| db.create_database_from_template("keep")).join());
+    let message = *res.unwrap_err().downcast::<String>().unwrap();
+    assert!(message.contains("already exists"), "{}", message);
+
+    assert_eq!(
+        db.query("SELECT datname::text FROM pg_database WHERE datname = 'keep'"),
+        [[Some("keep".to_string())]]
+    );
+}
@@ -0,0 +1,93 @@
+//! Tests for TLS with generated certificates. sqlx is built without TLS support, so these use
+//! psql.
+#![cfg(feature = "tls")]
+
+use pgtemp::{PgTempDB, PgTempDBBuilder};
+
+/// Run a query with psql using the given connection string and return its output
+fn psql(db: &PgTempDB, conn: &str, sql: &str) -> String {
+    let output = db
+        .command("psql")
+        .arg(conn)
+        .args([
+            "--no-psqlrc",
+            "--tuples-only",
+            "--no-align",
+            "--command",
+            sql,
+        ])
+        .output()
+        .expect("failed to run psql");
+    assert!(
+        output.status.success(),
+        "psql failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+const SSL_QUERY: &str = "SELECT ssl, client_dn FROM pg_stat_ssl WHERE pid = pg_backend_pid()";
+
+#[test]
+/// connections verify the server's certificate against the generated CA
+fn tls_verify_full() {
+    let db = PgTempDBBuilder::new().with_tls().start();
+
+    let ca_cert = db.tls_ca_cert().expect("no CA certificate");
+    assert!(ca_cert.exists());
+
+    let uri = db.connection_uri();
+    assert!(uri.contains("sslmode=verify-full"));
+    assert!(uri.contains("sslrootcert="));
+
+    assert_eq!(psql(&db, &uri, SSL_QUERY), "t|");
+    assert_eq!(psql(&db, &db.connection_string(), SSL_QUERY), "t|");
+
+    // the server still accepts connections without TLS
+    let conn = format!("{} sslmode=disable", db.connection_string());
+    assert_eq!(psql(&db, &conn, SSL_QUERY), "f|");
+}
+
+#[test]
+/// client certificates are signed by the same CA and presented to the server
+fn tls_client_cert() {
+    let db =
+        PgTempDBBuilder::from_connection_uri("postgres://localhost/tlsdb?sslmode=require").start();
+    let client_cert = db.issue_client_cert("postgres");
+
+    let conn = format!(
+        "{} sslcert={} sslkey={}",
+        db.connection_string(),
+        client_cert.cert_path.display(),
+        client_cert.key_path.display()
+    );
+    assert_eq!(psql(&db, &conn, SSL_QUERY), "t|/CN=postgres");
+}
+
+#[test]
+/// databases created on a TLS server also verify its certificate
+fn tls_created_databases() {
+    let db = PgTempDBBuilder::new().with_tls().start();
+    let clone = db.create_database_from_template("tls_clone");
+    assert!(clone.connection_uri().contains("sslmode=verify-full"));
+    assert_eq!(psql(&db, &clone.connection_uri(), SSL_QUERY), "t|");
+    drop(clone);
+
+    let cluster = PgTempDBBuilder::new().with_tls().start_cluster();
+    let cluster_db = cluster.create_database();
+    assert_eq!(
+        psql(cluster.server(), &cluster_db.connection_string(), SSL_QUERY),
+        "t|"
+    );
+    assert!(cluster_db
+        .connection_string()
+        .contains("sslmode=verify-full"));
+}
+
+#[test]
+/// TLS is off unless enabled
+fn tls_disabled_by_default() {
+    let db = PgTempDB::new();
+    assert!(db.tls_ca_cert().is_none());
+    assert!(!db.connection_uri().contains("sslmode"));
+}
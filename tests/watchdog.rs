@@ -0,0 +1,86 @@
+//! Tests for detecting postgres crashes
+
+use std::os::unix::process::ExitStatusExt;
+use std::time::Duration;
+
+use pgtemp::{PgTempDB, PgTempDBBuilder, WatchdogAction};
+use sqlx::postgres::PgConnection;
+use sqlx::prelude::*;
+
+/// Kill the postgres server with SIGKILL, as e.g. the OOM killer would
+fn kill_server(db: &PgTempDB) {
+    let pid_file = std::fs::read_to_string(db.data_dir().join("postmaster.pid"))
+        .expect("failed to read postmaster.pid");
+    let pid = pid_file.lines().next().unwrap();
+    let status = std::process::Command::new("kill")
+        .args(["-9", pid])
+        .status()
+        .expect("failed to run kill");
+    assert!(status.success());
+}
+
+#[tokio::test]
+/// a healthy server is running, and shutting it down is not reported as a crash
+async fn running_server_is_running() {
+    let mut db = PgTempDBBuilder::new()
+        .with_watchdog(WatchdogAction::Panic)
+        .start_async()
+        .await;
+    assert!(db.is_running());
+
+    let mut conn = PgConnection::connect(&db.connection_uri())
+        .await
+        .expect("failed to connect to db");
+    let row = sqlx::query("SELECT 1")
+        .fetch_one(&mut conn)
+        .await
+        .expect("failed to execute query");
+    let one: i32 = row.get(0);
+    assert_eq!(one, 1);
+    drop(conn);
+
+    db.async_shutdown().await;
+}
+
+#[test]
+/// a killed server is detected and its exit status is available
+fn killed_server_is_not_running() {
+    let mut db = PgTempDBBuilder::new().start();
+    kill_server(&db);
+
+    let status = db.wait_for_exit();
+    assert_eq!(status.signal(), Some(9));
+    assert!(!db.is_running());
+}
+
+#[test]
+#[should_panic(expected = "exited unexpectedly (killed by signal 9)")]
+/// the watchdog reports the crash the next time the connection details are used
+fn watchdog_panics_on_next_access() {
+    let temp = tempfile::tempdir().unwrap();
+    let db = PgTempDBBuilder::new()
+        .with_log_file(temp.path().join("postgres.log"))
+        .with_watchdog(WatchdogAction::Panic)
+        .start();
+    kill_server(&db);
+
+    for _ in 0..100 {
+        let _uri = db.connection_uri();
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+#[should_panic(expected = "last lines of server output")]
+/// the report includes the server's output even without a log file
+fn watchdog_reports_output_without_log_file() {
+    let db = PgTempDBBuilder::new()
+        .with_watchdog(WatchdogAction::Panic)
+        .start();
+    kill_server(&db);
+
+    for _ in 0..100 {
+        let _uri = db.connection_uri();
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}